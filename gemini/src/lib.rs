@@ -20,7 +20,8 @@ lazy_static::lazy_static! {
 }
 
 pub enum Message {
-    Chunk(String),
+    // raw body bytes, in the charset given with the MIME type
+    Chunk(Vec<u8>),
     MIME(String),
     Redirect(String),
     ErrorResponse(u8, String),
@@ -30,6 +31,14 @@ pub enum Message {
 }
 
 pub fn get(url: &str, chunk_callback: impl Fn(Message) -> ()) -> Result<()> {
+    get_while(url, |msg| {
+        chunk_callback(msg);
+        true
+    })
+}
+
+// like get, but stops reading as soon as the callback returns false
+pub fn get_while(url: &str, mut chunk_callback: impl FnMut(Message) -> bool) -> Result<()> {
     let url = Url::parse(&url).context("Cannot parse URL")?;
 
     let host = url.host_str().context("Url doesn't have host")?;
@@ -72,23 +81,20 @@ pub fn get(url: &str, chunk_callback: impl Fn(Message) -> ()) -> Result<()> {
             break;
         }
 
-        let data = String::from_utf8_lossy(&buf[0..len]);
+        let data = &buf[0..len];
         let content = if is_content {
             log::debug!("content, pass through");
-            data.as_ref()
+            data
         } else {
-            let mut split_data = data.splitn(2, "\r\n");
-            let header = split_data.next().context("No header received")?;
-
-            let mut header = header.split_whitespace();
+            // only the header is text, the body is decoded by whoever renders it
+            let header_end = data
+                .windows(2)
+                .position(|w| w == b"\r\n")
+                .unwrap_or(data.len());
 
-            let status = header
-                .next()
-                .context("No status given?")?
-                .parse::<u8>()
-                .context("Status isn't number 0-255")?;
-
-            let meta = header.next().context("No metadata given?")?;
+            let header = String::from_utf8_lossy(&data[..header_end]);
+            let (status, meta) = parse_header(&header)?;
+            let meta = meta.as_str();
 
             log::info!("{}: {} {}", url, status, meta);
             if status >= 20 && status < 30 {
@@ -103,15 +109,79 @@ pub fn get(url: &str, chunk_callback: impl Fn(Message) -> ()) -> Result<()> {
 
             is_content = true;
 
-            chunk_callback(Message::MIME(meta.to_owned()));
-            split_data.next().unwrap_or("")
+            if !chunk_callback(Message::MIME(meta.to_owned())) {
+                return Ok(());
+            }
+
+            data.get(header_end + 2..).unwrap_or(&[])
         }
-        .to_owned();
+        .to_vec();
 
-        chunk_callback(Message::Chunk(content));
+        if !chunk_callback(Message::Chunk(content)) {
+            return Ok(());
+        }
     }
 
+    // the body is passed on as is, renderers finish the last line themselves
     log::debug!("gemini machine broke");
-    chunk_callback(Message::Chunk("\n".to_owned()));
     break_response
 }
+
+// splits a response header into its status and meta. the meta is everything
+// after the first space, like "text/gemini; charset=utf-8"
+fn parse_header(header: &str) -> Result<(u8, String)> {
+    let mut header = header.splitn(2, ' ');
+
+    let status = header
+        .next()
+        .context("No status given?")?
+        .parse::<u8>()
+        .context("Status isn't number 0-255")?;
+
+    let meta = header.next().map(str::trim).unwrap_or_default();
+    if meta.is_empty() {
+        return Err(anyhow::anyhow!("No metadata given?"));
+    }
+
+    Ok((status, meta.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let cases = [
+            ("20 text/gemini", 20, "text/gemini"),
+            (
+                "20 text/plain; charset=iso-8859-1",
+                20,
+                "text/plain; charset=iso-8859-1",
+            ),
+            ("20 text/gemini;  lang=en ", 20, "text/gemini;  lang=en"),
+            (
+                "31 gemini://example.org/new",
+                31,
+                "gemini://example.org/new",
+            ),
+            ("51 Not found, sorry", 51, "Not found, sorry"),
+        ];
+
+        for (header, status, meta) in cases.iter() {
+            assert_eq!(
+                parse_header(header).unwrap(),
+                (*status, (*meta).to_owned()),
+                "{:?}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn bad_header() {
+        for header in &["", "20", "20 ", "abc text/gemini", "300 text/gemini"] {
+            assert!(parse_header(header).is_err(), "{:?}", header);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! A dependency-free `text/gemini` parser.
+//!
+//! Lines are fed one at a time to a [`Parser`], which turns them into typed
+//! [`Line`]s. Preformatted blocks are collected until their closing toggle line,
+//! so the parser can be driven from a network stream as data arrives.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Text(String),
+    Link { url: String, label: Option<String> },
+    Heading { level: u8, text: String },
+    ListItem(String),
+    Quote(String),
+    Preformatted { alt: Option<String>, lines: Vec<String> },
+}
+
+struct Block {
+    alt: Option<String>,
+    lines: Vec<String>,
+}
+
+pub struct Parser {
+    preformatted: Option<Block>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self { preformatted: None }
+    }
+
+    pub fn is_preformatted(&self) -> bool {
+        self.preformatted.is_some()
+    }
+
+    /// Parses a single line, without its line terminator.
+    ///
+    /// Returns `None` while inside a preformatted block, the whole block is
+    /// returned once its closing line is parsed.
+    pub fn parse_line(&mut self, line: &str) -> Option<Line> {
+        let line = line.trim_end_matches('\r');
+
+        if let Some(alt) = line.strip_prefix("```") {
+            return match self.preformatted.take() {
+                Some(block) => Some(Line::Preformatted {
+                    alt: block.alt,
+                    lines: block.lines,
+                }),
+                None => {
+                    let alt = alt.trim();
+
+                    self.preformatted = Some(Block {
+                        alt: if alt.is_empty() {
+                            None
+                        } else {
+                            Some(alt.to_owned())
+                        },
+                        lines: vec![],
+                    });
+
+                    None
+                }
+            };
+        }
+
+        if let Some(block) = &mut self.preformatted {
+            block.lines.push(line.to_owned());
+            return None;
+        }
+
+        // only preformat toggles and links may be followed by spaces, the
+        // text of any other line is kept as is
+        if let Some(link) = line.strip_prefix("=>") {
+            let data = &mut link.split_whitespace();
+
+            match data.next() {
+                Some(url) => {
+                    let label = data.collect::<Vec<&str>>().join(" ");
+
+                    Some(Line::Link {
+                        url: url.to_owned(),
+                        label: if label.is_empty() { None } else { Some(label) },
+                    })
+                }
+                None => Some(Line::Text(line.to_owned())),
+            }
+        } else if let Some(item) = line.strip_prefix("* ") {
+            Some(Line::ListItem(item.trim().to_owned()))
+        } else if let Some(quote) = line.strip_prefix('>') {
+            Some(Line::Quote(quote.trim().to_owned()))
+        } else if line.starts_with('#') {
+            // only up to three levels exist, any extra `#` is part of the text
+            let level = line.chars().take(3).take_while(|c| *c == '#').count();
+
+            Some(Line::Heading {
+                level: level as u8,
+                text: line[level..].trim().to_owned(),
+            })
+        } else {
+            Some(Line::Text(line.to_owned()))
+        }
+    }
+
+    /// Ends the document, returning any preformatted block left unterminated.
+    pub fn finish(&mut self) -> Option<Line> {
+        self.preformatted.take().map(|block| Line::Preformatted {
+            alt: block.alt,
+            lines: block.lines,
+        })
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a complete document.
+pub fn parse(source: &str) -> Vec<Line> {
+    let mut parser = Parser::new();
+
+    let mut lines: Vec<Line> = source
+        .lines()
+        .filter_map(|line| parser.parse_line(line))
+        .collect();
+
+    lines.extend(parser.finish());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Line {
+        Line::Text(text.to_owned())
+    }
+
+    #[test]
+    fn line_types() {
+        let cases = vec![
+            ("plain text", text("plain text")),
+            ("  indented text", text("  indented text")),
+            ("", text("")),
+            ("text\r", text("text")),
+            (
+                "=> gemini://example.org",
+                Line::Link {
+                    url: "gemini://example.org".to_owned(),
+                    label: None,
+                },
+            ),
+            (
+                "=>  /path   a   label ",
+                Line::Link {
+                    url: "/path".to_owned(),
+                    label: Some("a label".to_owned()),
+                },
+            ),
+            ("=>", text("=>")),
+            (" => not a link", text(" => not a link")),
+            ("* item", Line::ListItem("item".to_owned())),
+            ("*  spaced item ", Line::ListItem("spaced item".to_owned())),
+            ("*emphasis*", text("*emphasis*")),
+            ("*", text("*")),
+            ("> quote", Line::Quote("quote".to_owned())),
+            (">quote", Line::Quote("quote".to_owned())),
+            (
+                "# one",
+                Line::Heading {
+                    level: 1,
+                    text: "one".to_owned(),
+                },
+            ),
+            (
+                "##two",
+                Line::Heading {
+                    level: 2,
+                    text: "two".to_owned(),
+                },
+            ),
+            (
+                "#### four",
+                Line::Heading {
+                    level: 3,
+                    text: "# four".to_owned(),
+                },
+            ),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(Parser::new().parse_line(line), Some(expected), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn preformatted() {
+        let cases = vec![
+            ("```\n```", None, vec![]),
+            (
+                "``` rust \nfn main() {}\n```",
+                Some("rust"),
+                vec!["fn main() {}"],
+            ),
+            (
+                "```\n  indented\n\ttab  \n=> not a link\n* not an item\n```ignored",
+                None,
+                vec!["  indented", "\ttab  ", "=> not a link", "* not an item"],
+            ),
+            // left open until the end of the document
+            ("```\n# not a heading", None, vec!["# not a heading"]),
+        ];
+
+        for (source, alt, lines) in cases {
+            let expected = Line::Preformatted {
+                alt: alt.map(str::to_owned),
+                lines: lines.into_iter().map(str::to_owned).collect(),
+            };
+
+            assert_eq!(parse(source), vec![expected], "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn toggles() {
+        let mut parser = Parser::new();
+
+        assert_eq!(parser.parse_line("```"), None);
+        assert!(parser.is_preformatted());
+        assert_eq!(parser.parse_line("text"), None);
+        assert!(parser.parse_line("```").is_some());
+        assert!(!parser.is_preformatted());
+        assert_eq!(parser.parse_line("text"), Some(text("text")));
+        assert_eq!(parser.finish(), None);
+    }
+}
@@ -0,0 +1,22 @@
+// what a line is, for screen readers
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Role {
+    Text,
+    Heading(u8),
+    Link,
+    ListItem,
+    Quote,
+    Preformatted,
+    Image,
+}
+
+pub struct AccessibleLine {
+    pub role: Role,
+    pub text: String,
+
+    // link target for links, alt text for preformatted blocks, size for
+    // images
+    pub description: Option<String>,
+    // whether it can get the keyboard focus, like links
+    pub focusable: bool,
+}
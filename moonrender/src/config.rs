@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Color(pub u8, pub u8, pub u8);
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -10,15 +10,224 @@ pub struct Section {
     pub size: f64,
     pub line_spacing: f64,
 }
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FontWeight {
+    Thin,
+    Ultralight,
+    Light,
+    Normal,
+    Medium,
+    Semibold,
+    Bold,
+    Ultrabold,
+    Heavy,
+    Ultraheavy,
+}
+
+impl From<FontWeight> for pango::Weight {
+    fn from(weight: FontWeight) -> Self {
+        match weight {
+            FontWeight::Thin => pango::Weight::Thin,
+            FontWeight::Ultralight => pango::Weight::Ultralight,
+            FontWeight::Light => pango::Weight::Light,
+            FontWeight::Normal => pango::Weight::Normal,
+            FontWeight::Medium => pango::Weight::Medium,
+            FontWeight::Semibold => pango::Weight::Semibold,
+            FontWeight::Bold => pango::Weight::Bold,
+            FontWeight::Ultrabold => pango::Weight::Ultrabold,
+            FontWeight::Heavy => pango::Weight::Heavy,
+            FontWeight::Ultraheavy => pango::Weight::Ultraheavy,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
-pub struct HeadingSection {
+pub struct HeadingLevel {
     pub font: String,
     pub color: Color,
-
-    pub level1_size: f64,
-    pub level2_size: f64,
+    pub size: f64,
+    pub weight: FontWeight,
 
     pub line_spacing: f64,
+    pub spacing_above: f64,
+    pub spacing_below: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "OldHeadingSection")]
+pub struct HeadingSection {
+    pub level1: HeadingLevel,
+    pub level2: HeadingLevel,
+    pub level3: HeadingLevel,
+    // only markdown goes this deep
+    pub level4: HeadingLevel,
+    pub level5: HeadingLevel,
+    pub level6: HeadingLevel,
+}
+
+impl HeadingSection {
+    fn levels_mut(&mut self) -> [&mut HeadingLevel; 6] {
+        [
+            &mut self.level1,
+            &mut self.level2,
+            &mut self.level3,
+            &mut self.level4,
+            &mut self.level5,
+            &mut self.level6,
+        ]
+    }
+
+    pub fn level(&self, level: u8) -> &HeadingLevel {
+        match level {
+            1 => &self.level1,
+            2 => &self.level2,
+            3 => &self.level3,
+            4 => &self.level4,
+            5 => &self.level5,
+            _ => &self.level6,
+        }
+    }
+}
+
+impl Default for HeadingSection {
+    fn default() -> Self {
+        Self {
+            level1: HeadingLevel {
+                font: "sans-serif".to_owned(),
+                color: Color(0, 0, 0),
+                size: 27.0,
+                weight: FontWeight::Ultraheavy,
+
+                line_spacing: 1.5,
+                spacing_above: 8.0,
+                spacing_below: 2.0,
+            },
+
+            level2: HeadingLevel {
+                font: "sans-serif".to_owned(),
+                color: Color(0, 0, 0),
+                size: 20.0,
+                weight: FontWeight::Ultraheavy,
+
+                line_spacing: 1.5,
+                spacing_above: 6.0,
+                spacing_below: 2.0,
+            },
+
+            level3: HeadingLevel {
+                font: "sans-serif".to_owned(),
+                color: Color(0, 0, 0),
+                size: 16.5,
+                weight: FontWeight::Bold,
+
+                line_spacing: 1.5,
+                spacing_above: 4.0,
+                spacing_below: 1.0,
+            },
+
+            level4: HeadingLevel {
+                font: "sans-serif".to_owned(),
+                color: Color(0, 0, 0),
+                size: 14.5,
+                weight: FontWeight::Bold,
+
+                line_spacing: 1.5,
+                spacing_above: 4.0,
+                spacing_below: 1.0,
+            },
+
+            level5: HeadingLevel {
+                font: "sans-serif".to_owned(),
+                color: Color(0, 0, 0),
+                size: 13.5,
+                weight: FontWeight::Bold,
+
+                line_spacing: 1.5,
+                spacing_above: 2.0,
+                spacing_below: 1.0,
+            },
+
+            level6: HeadingLevel {
+                font: "sans-serif".to_owned(),
+                color: Color(64, 64, 64),
+                size: 13.5,
+                weight: FontWeight::Semibold,
+
+                line_spacing: 1.5,
+                spacing_above: 2.0,
+                spacing_below: 1.0,
+            },
+        }
+    }
+}
+
+// the heading section as it's read, with the keys it had before headings
+// were styled per level. those still apply, to every level or to the level
+// they name, unless the level's own table is there
+#[derive(Deserialize, Default)]
+struct OldHeadingSection {
+    level1: Option<HeadingLevel>,
+    level2: Option<HeadingLevel>,
+    level3: Option<HeadingLevel>,
+    level4: Option<HeadingLevel>,
+    level5: Option<HeadingLevel>,
+    level6: Option<HeadingLevel>,
+
+    font: Option<String>,
+    color: Option<Color>,
+    level1_size: Option<f64>,
+    level2_size: Option<f64>,
+    line_spacing: Option<f64>,
+}
+
+impl From<OldHeadingSection> for HeadingSection {
+    fn from(old: OldHeadingSection) -> Self {
+        let mut section = Self::default();
+
+        let has_old_keys = old.font.is_some()
+            || old.color.is_some()
+            || old.level1_size.is_some()
+            || old.level2_size.is_some()
+            || old.line_spacing.is_some();
+
+        if has_old_keys {
+            log::warn!(
+                "theme.heading font, color, level1_size, level2_size and line_spacing are \
+                 deprecated, use the theme.heading.level1 to level6 tables instead"
+            );
+        }
+
+        for level in section.levels_mut().iter_mut() {
+            if let Some(font) = &old.font {
+                level.font = font.clone();
+            }
+            if let Some(color) = &old.color {
+                level.color = color.clone();
+            }
+            if let Some(line_spacing) = old.line_spacing {
+                level.line_spacing = line_spacing;
+            }
+        }
+
+        if let Some(size) = old.level1_size {
+            section.level1.size = size;
+        }
+        if let Some(size) = old.level2_size {
+            section.level2.size = size;
+        }
+
+        let levels = [
+            old.level1, old.level2, old.level3, old.level4, old.level5, old.level6,
+        ];
+        for (level, new) in section.levels_mut().iter_mut().zip(levels.iter()) {
+            if let Some(new) = new {
+                **level = new.clone();
+            }
+        }
+
+        section
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -34,6 +243,92 @@ pub struct ListSection {
 }
 
 #[derive(Serialize, Deserialize, Clone)]
+pub struct QuoteSection {
+    pub font: String,
+    pub color: Color,
+
+    pub size: f64,
+    pub indent: f64,
+
+    pub rule_color: Color,
+    pub rule_width: f64,
+
+    pub line_spacing: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HighlightSection {
+    pub enabled: bool,
+
+    pub comment: Color,
+    pub string: Color,
+    pub number: Color,
+    pub constant: Color,
+    pub keyword: Color,
+    pub type_name: Color,
+    pub function: Color,
+}
+
+impl Default for HighlightSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+
+            comment: Color(128, 128, 128),
+            string: Color(0, 128, 0),
+            number: Color(152, 104, 1),
+            constant: Color(152, 104, 1),
+            keyword: Color(167, 29, 93),
+            type_name: Color(0, 92, 197),
+            function: Color(111, 66, 193),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SelectionSection {
+    pub foreground: Color,
+    pub background: Color,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FindSection {
+    pub foreground: Color,
+    pub background: Color,
+    pub current_background: Color,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FocusSection {
+    pub color: Color,
+    pub width: f64,
+    pub padding: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HintSection {
+    pub font: String,
+    pub size: f64,
+    pub foreground: Color,
+    pub background: Color,
+    pub typed_color: Color,
+    pub padding: f64,
+}
+
+// whether links to images show the image below them
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum InlineImages {
+    Off,
+    // loaded once they're scrolled into view
+    Auto,
+    // loaded once their placeholder is clicked, to save bandwidth
+    Click,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Theme {
     pub margin: f64,
     pub max_content_width: f64,
@@ -46,6 +341,83 @@ pub struct Theme {
     pub monospace: Section,
     pub heading: HeadingSection,
     pub list: ListSection,
+    pub quote: QuoteSection,
+    pub highlight: HighlightSection,
+    pub selection: SelectionSection,
+    pub find: FindSection,
+    pub focus: FocusSection,
+    pub hints: HintSection,
+
+    pub inline_images: InlineImages,
+
+    // memory for drawn page tiles, in megabytes
+    pub tile_cache_size: usize,
+}
+
+impl Theme {
+    // black on white, without link colors or syntax highlighting
+    pub fn print() -> Self {
+        let theme = Self::default();
+        let black = Color(0, 0, 0);
+
+        Self {
+            background_color: Color(255, 255, 255),
+
+            content: Section {
+                color: black.clone(),
+                ..theme.content
+            },
+            link: Section {
+                color: black.clone(),
+                ..theme.link
+            },
+            monospace: Section {
+                color: black.clone(),
+                ..theme.monospace
+            },
+            heading: HeadingSection {
+                level1: HeadingLevel {
+                    color: black.clone(),
+                    ..theme.heading.level1
+                },
+                level2: HeadingLevel {
+                    color: black.clone(),
+                    ..theme.heading.level2
+                },
+                level3: HeadingLevel {
+                    color: black.clone(),
+                    ..theme.heading.level3
+                },
+                level4: HeadingLevel {
+                    color: black.clone(),
+                    ..theme.heading.level4
+                },
+                level5: HeadingLevel {
+                    color: black.clone(),
+                    ..theme.heading.level5
+                },
+                level6: HeadingLevel {
+                    color: black.clone(),
+                    ..theme.heading.level6
+                },
+            },
+            list: ListSection {
+                color: black.clone(),
+                ..theme.list
+            },
+            quote: QuoteSection {
+                color: black,
+                rule_color: Color(128, 128, 128),
+                ..theme.quote
+            },
+            highlight: HighlightSection {
+                enabled: false,
+                ..theme.highlight
+            },
+
+            ..theme
+        }
+    }
 }
 
 impl Default for Theme {
@@ -79,15 +451,7 @@ impl Default for Theme {
                 line_spacing: 1.0,
             },
 
-            heading: HeadingSection {
-                font: "sans-serif".to_owned(),
-                color: Color(0, 0, 0),
-
-                level1_size: 27.0,
-                level2_size: 20.0,
-
-                line_spacing: 1.5,
-            },
+            heading: HeadingSection::default(),
 
             list: ListSection {
                 bullet: "•".to_owned(),
@@ -97,6 +461,100 @@ impl Default for Theme {
                 bullet_padding: 4.0,
                 line_spacing: 2.5,
             },
+
+            quote: QuoteSection {
+                font: "sans-serif".to_owned(),
+                color: Color(64, 64, 64),
+
+                size: 13.5,
+                indent: 16.0,
+
+                rule_color: Color(192, 192, 192),
+                rule_width: 3.0,
+
+                line_spacing: 2.5,
+            },
+
+            highlight: HighlightSection::default(),
+
+            selection: SelectionSection {
+                foreground: Color(255, 255, 255),
+                background: Color(53, 132, 228),
+            },
+
+            find: FindSection {
+                foreground: Color(0, 0, 0),
+                background: Color(252, 233, 79),
+                current_background: Color(245, 121, 0),
+            },
+
+            focus: FocusSection {
+                color: Color(53, 132, 228),
+                width: 2.0,
+                padding: 2.0,
+            },
+
+            hints: HintSection {
+                font: "monospace bold".to_owned(),
+                size: 10.0,
+                foreground: Color(0, 0, 0),
+                background: Color(252, 233, 79),
+                typed_color: Color(196, 160, 0),
+                padding: 2.0,
+            },
+
+            inline_images: InlineImages::Off,
+
+            tile_cache_size: 64,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_heading_keys() {
+        let section = HeadingSection::from(OldHeadingSection {
+            font: Some("serif".to_owned()),
+            color: Some(Color(1, 2, 3)),
+            level1_size: Some(30.0),
+            level2_size: Some(22.0),
+            line_spacing: Some(2.0),
+            ..OldHeadingSection::default()
+        });
+
+        for level in 1..=6 {
+            let level = section.level(level);
+            assert_eq!(level.font, "serif");
+            assert_eq!(level.color, Color(1, 2, 3));
+            assert_eq!(level.line_spacing, 2.0);
+        }
+
+        let default = HeadingSection::default();
+        assert_eq!(section.level(1).size, 30.0);
+        assert_eq!(section.level(2).size, 22.0);
+        assert_eq!(section.level(3).size, default.level(3).size);
+    }
+
+    #[test]
+    fn level_tables_win() {
+        let level2 = HeadingLevel {
+            size: 40.0,
+            ..HeadingSection::default().level2
+        };
+
+        let section = HeadingSection::from(OldHeadingSection {
+            level2: Some(level2),
+            level2_size: Some(22.0),
+            ..OldHeadingSection::default()
+        });
+
+        assert_eq!(section.level(2).size, 40.0);
+        assert_eq!(
+            section.level(1).size,
+            HeadingSection::default().level(1).size
+        );
+    }
+}
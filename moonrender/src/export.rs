@@ -0,0 +1,126 @@
+use anyhow::{anyhow, bail, Context as _, Result};
+use cairo::{Context, Format, ImageSurface, PdfSurface, SvgSurface};
+use std::{fs::File, path::Path};
+
+// cairo can't create image surfaces larger than this
+const MAX_IMAGE_SIZE: f64 = 32767.0;
+
+// pdf pages are as wide as the export, and as tall as an A4 page of that width
+pub const PAGE_RATIO: f64 = 297.0 / 210.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Png,
+    Pdf,
+    Svg,
+}
+
+impl ExportFormat {
+    // guesses the format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "pdf" => Some(Self::Pdf),
+            "svg" => Some(Self::Svg),
+            _ => None,
+        }
+    }
+}
+
+// creates a surface of the given format and size, lets `draw` paint on it and
+// writes it to path
+pub fn write(
+    format: ExportFormat,
+    (width, height): (f64, f64),
+    path: &Path,
+    mut draw: impl FnMut(&Context),
+) -> Result<()> {
+    match format {
+        ExportFormat::Png => {
+            check_image_size(width, height)?;
+
+            let surface = ImageSurface::create(Format::ARgb32, width as i32, height as i32)
+                .map_err(|e| anyhow!("Cannot create image surface: {:?}", e))?;
+
+            draw(&Context::new(&surface));
+
+            let mut file = File::create(path).context("Cannot create PNG file")?;
+            surface
+                .write_to_png(&mut file)
+                .context("Cannot write PNG file")?;
+        }
+
+        ExportFormat::Pdf => write_pdf((width, height), path, 1, |ctx, _| draw(ctx))?,
+
+        ExportFormat::Svg => {
+            let surface = SvgSurface::new(width, height, Some(path))
+                .map_err(|e| anyhow!("Cannot create SVG surface: {:?}", e))?;
+
+            draw(&Context::new(&surface));
+            surface.finish();
+        }
+    }
+
+    Ok(())
+}
+
+// writes a pdf with the given number of pages of the same size, `draw` paints
+// each of them by number
+pub fn write_pdf(
+    (width, height): (f64, f64),
+    path: &Path,
+    pages: usize,
+    mut draw: impl FnMut(&Context, usize),
+) -> Result<()> {
+    let surface = PdfSurface::new(width, height, path)
+        .map_err(|e| anyhow!("Cannot create PDF surface: {:?}", e))?;
+    let ctx = Context::new(&surface);
+
+    for page in 0..pages {
+        ctx.save();
+        draw(&ctx, page);
+        ctx.restore();
+
+        ctx.show_page();
+    }
+
+    surface.finish();
+    Ok(())
+}
+
+fn check_image_size(width: f64, height: f64) -> Result<()> {
+    if height > MAX_IMAGE_SIZE {
+        bail!(
+            "Page too tall for PNG ({} pixels, at most {}), export to PDF or SVG instead",
+            height.ceil(),
+            MAX_IMAGE_SIZE
+        );
+    }
+
+    if width > MAX_IMAGE_SIZE {
+        bail!(
+            "Page too wide for PNG, it can be at most {} pixels",
+            MAX_IMAGE_SIZE
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_sizes() {
+        assert!(check_image_size(800.0, 32767.0).is_ok());
+
+        let error = check_image_size(800.0, 40000.5).unwrap_err().to_string();
+        assert!(error.starts_with("Page too tall for PNG"), "{}", error);
+        assert!(error.contains("40001 pixels"), "{}", error);
+
+        assert!(check_image_size(40000.0, 100.0).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::selection::is_word_char;
+
+#[derive(Clone, Copy, Default)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Match {
+    pub line: usize,
+
+    // byte range in the line's text
+    pub start: usize,
+    pub end: usize,
+}
+
+// length of the match if haystack starts with query
+fn match_len(haystack: &str, query: &str, case_sensitive: bool) -> Option<usize> {
+    if case_sensitive {
+        return if haystack.starts_with(query) {
+            Some(query.len())
+        } else {
+            None
+        };
+    }
+
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+
+    for (i, chr) in haystack.char_indices() {
+        if query.peek().is_none() {
+            return Some(i);
+        }
+
+        for lower in chr.to_lowercase() {
+            if query.next() != Some(lower) {
+                return None;
+            }
+        }
+    }
+
+    if query.peek().is_none() {
+        Some(haystack.len())
+    } else {
+        None
+    }
+}
+
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+
+    !before.map(is_word_char).unwrap_or(false) && !after.map(is_word_char).unwrap_or(false)
+}
+
+// byte ranges of every occurrence of query in text
+pub fn find_in(text: &str, query: &str, options: FindOptions) -> Vec<(usize, usize)> {
+    let mut matches = vec![];
+
+    if query.is_empty() {
+        return matches;
+    }
+
+    let mut skip_until = 0;
+    for (start, _) in text.char_indices() {
+        if start < skip_until {
+            continue;
+        }
+
+        if let Some(len) = match_len(&text[start..], query, options.case_sensitive) {
+            let end = start + len;
+
+            if !options.whole_word || is_whole_word(text, start, end) {
+                matches.push((start, end));
+                skip_until = end;
+            }
+        }
+    }
+
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(
+        text: &str,
+        query: &str,
+        case_sensitive: bool,
+        whole_word: bool,
+    ) -> Vec<(usize, usize)> {
+        let options = FindOptions {
+            case_sensitive,
+            whole_word,
+        };
+
+        find_in(text, query, options)
+    }
+
+    #[test]
+    fn case() {
+        assert_eq!(
+            find("Moon moon MOON", "moon", false, false),
+            [(0, 4), (5, 9), (10, 14)]
+        );
+        assert_eq!(find("Moon moon MOON", "moon", true, false), [(5, 9)]);
+        assert_eq!(find("Moon moon MOON", "MoOn", false, false).len(), 3);
+    }
+
+    #[test]
+    fn folded_lengths() {
+        // the capital sharp s lowercases to ß, which is two bytes to its three
+        assert_eq!(find("Maß", "MAẞ", false, false), [(0, 4)]);
+        assert_eq!(find("MAẞ", "maß", false, false), [(0, 5)]);
+
+        // İ lowercases to i and a combining dot, two characters
+        assert_eq!(find("İstanbul", "i\u{307}stanbul", false, false), [(0, 9)]);
+        assert_eq!(find("i\u{307}x", "İ", false, false), [(0, 3)]);
+
+        // a match can't end inside of what one character lowercases to
+        assert_eq!(find("İstanbul", "istanbul", false, false), []);
+        assert_eq!(find("İ", "i", false, false), []);
+
+        // and ranges always fall on character boundaries
+        assert_eq!(find("日本語の本", "本", false, false), [(3, 6), (12, 15)]);
+    }
+
+    #[test]
+    fn overlapping_and_empty() {
+        // matches don't overlap, the search goes on after each one
+        assert_eq!(find("aaaa", "aa", false, false), [(0, 2), (2, 4)]);
+        assert_eq!(find("aaa", "aa", false, false), [(0, 2)]);
+
+        assert_eq!(find("text", "", false, false), []);
+        assert_eq!(find("", "text", false, false), []);
+        assert_eq!(find("", "", false, false), []);
+        assert_eq!(find("te", "text", false, false), []);
+    }
+
+    #[test]
+    fn whole_words() {
+        assert_eq!(
+            find("cat concat cat.", "cat", false, true),
+            [(0, 3), (11, 14)]
+        );
+        assert_eq!(find("cat_cat", "cat", false, true), []);
+        assert_eq!(find("écat", "cat", false, true), []);
+    }
+}
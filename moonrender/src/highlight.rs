@@ -0,0 +1,97 @@
+use crate::config::{Color, HighlightSection};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{self, StyleModifier, ThemeItem, ThemeSettings},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+lazy_static::lazy_static! {
+    static ref SYNTAXES: SyntaxSet = SyntaxSet::load_defaults_newlines();
+}
+
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+
+    pub color: Color,
+}
+
+fn to_syntect_color(color: &Color) -> highlighting::Color {
+    highlighting::Color {
+        r: color.0,
+        g: color.1,
+        b: color.2,
+        a: 0xFF,
+    }
+}
+
+fn to_syntect_theme(section: &HighlightSection, foreground: &Color) -> highlighting::Theme {
+    let item = |scope: &str, color: &Color| ThemeItem {
+        scope: scope.parse().expect("Invalid highlight scope selector"),
+        style: StyleModifier {
+            foreground: Some(to_syntect_color(color)),
+            background: None,
+            font_style: None,
+        },
+    };
+
+    highlighting::Theme {
+        settings: ThemeSettings {
+            foreground: Some(to_syntect_color(foreground)),
+            ..ThemeSettings::default()
+        },
+
+        scopes: vec![
+            item("comment", &section.comment),
+            item("string", &section.string),
+            item("constant.numeric", &section.number),
+            item(
+                "constant.language, constant.character, support.constant",
+                &section.constant,
+            ),
+            item("keyword, storage.modifier", &section.keyword),
+            item(
+                "storage.type, entity.name.type, entity.name.class, support.type",
+                &section.type_name,
+            ),
+            item(
+                "entity.name.function, support.function, variable.function",
+                &section.function,
+            ),
+        ],
+
+        ..highlighting::Theme::default()
+    }
+}
+
+// `language` is matched against syntax names and file extensions, like "rust"
+// or "rs". returns None if there's no syntax for the language.
+pub fn highlight(
+    text: &str,
+    language: &str,
+    section: &HighlightSection,
+    foreground: &Color,
+) -> Option<Vec<Span>> {
+    let syntax = SYNTAXES.find_syntax_by_token(language)?;
+    let theme = to_syntect_theme(section, foreground);
+
+    let mut highlighter = HighlightLines::new(syntax, &theme);
+    let mut spans = vec![];
+    let mut offset = 0;
+
+    for line in LinesWithEndings::from(text) {
+        for (style, token) in highlighter.highlight(line, &SYNTAXES) {
+            spans.push(Span {
+                start: offset,
+                end: offset + token.len(),
+
+                color: Color(style.foreground.r, style.foreground.g, style.foreground.b),
+            });
+
+            offset += token.len();
+        }
+    }
+
+    Some(spans)
+}
@@ -0,0 +1,82 @@
+use crate::Theme;
+use cairo::Context;
+use pango::{AttrList, Attribute};
+
+// home row first, since those are the easiest to type
+const HINT_CHARS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+pub struct Hint {
+    pub label: String,
+    pub line: usize,
+}
+
+// labels of the same length, so no label is the prefix of another one
+pub fn labels(count: usize) -> Vec<String> {
+    let chars: Vec<char> = HINT_CHARS.chars().collect();
+
+    let mut len = 1;
+    while chars.len().pow(len as u32) < count {
+        len += 1;
+    }
+
+    (0..count)
+        .map(|mut i| {
+            let mut label = vec![chars[0]; len];
+
+            for chr in label.iter_mut().rev() {
+                *chr = chars[i % chars.len()];
+                i /= chars.len();
+            }
+
+            label.into_iter().collect()
+        })
+        .collect()
+}
+
+// draws the label over the start of a link, with the part that was already
+// typed in a different color
+pub fn draw(ctx: &Context, theme: &Theme, label: &str, typed: usize, (x, y): (f64, f64)) {
+    let layout = pangocairo::create_layout(ctx).expect("cannot create pango layout");
+
+    let mut font_description = pango::FontDescription::from_string(&theme.hints.font);
+    font_description.set_size(pango::units_from_double(theme.hints.size));
+
+    layout.set_font_description(Some(&font_description));
+    layout.set_text(label);
+
+    let color = &theme.hints.typed_color;
+    if let Some(mut attr) = Attribute::new_foreground(
+        color.0 as u16 * 257,
+        color.1 as u16 * 257,
+        color.2 as u16 * 257,
+    ) {
+        let attrs = AttrList::new();
+
+        attr.set_start_index(0);
+        attr.set_end_index(typed as u32);
+        attrs.insert(attr);
+
+        layout.set_attributes(Some(&attrs));
+    }
+
+    let (w, h) = layout.get_pixel_size();
+    let padding = theme.hints.padding;
+
+    let (w, h) = (w as f64 + padding * 2.0, h as f64 + padding * 2.0);
+
+    ctx.set_source_rgb(
+        theme.hints.background.0 as f64 / 255.0,
+        theme.hints.background.1 as f64 / 255.0,
+        theme.hints.background.2 as f64 / 255.0,
+    );
+    ctx.rectangle(x, y, w, h);
+    ctx.fill();
+
+    ctx.set_source_rgb(
+        theme.hints.foreground.0 as f64 / 255.0,
+        theme.hints.foreground.1 as f64 / 255.0,
+        theme.hints.foreground.2 as f64 / 255.0,
+    );
+    ctx.move_to(x + padding, y + padding);
+    pangocairo::show_layout(ctx, &layout);
+}
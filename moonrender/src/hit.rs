@@ -0,0 +1,136 @@
+use std::{cmp::Ordering, ops::Range};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    pub fn contains(&self, (x, y): (f64, f64)) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.bottom()
+    }
+}
+
+// what's under a point in the page
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hit {
+    pub line: usize,
+
+    // byte index in the line's text, for lines with text
+    pub index: Option<usize>,
+    // absolute url of the link under the point, if there's one
+    pub link: Option<String>,
+}
+
+// rectangles of every line in page order, built while measuring. lines are
+// placed top to bottom without overlapping, so they can be binary searched
+#[derive(Default)]
+pub struct LineIndex {
+    rects: Vec<Rect>,
+}
+
+impl LineIndex {
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+
+    pub fn push(&mut self, rect: Rect) {
+        self.rects.push(rect);
+    }
+
+    // index of the first line that ends at or below y
+    pub fn first_below(&self, y: f64) -> usize {
+        self.rects
+            .binary_search_by(|rect| {
+                if rect.bottom() < y {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
+            .unwrap_or_else(|i| i)
+    }
+
+    // the line that contains pos
+    pub fn line_at(&self, pos: (f64, f64)) -> Option<usize> {
+        let i = self.first_below(pos.1);
+        self.rects
+            .get(i)
+            .filter(|rect| rect.contains(pos))
+            .map(|_| i)
+    }
+
+    // lines that are at least partly between top and bottom
+    pub fn lines_between(&self, top: f64, bottom: f64) -> Range<usize> {
+        let start = self.first_below(top);
+        let end = start
+            + self.rects[start..]
+                .iter()
+                .take_while(|rect| rect.y <= bottom)
+                .count();
+
+        start..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // three lines 10 high, with a gap of 5 between the last two
+    fn index() -> LineIndex {
+        let mut index = LineIndex::default();
+
+        for y in &[0.0, 10.0, 25.0] {
+            index.push(Rect {
+                x: 0.0,
+                y: *y,
+                width: 100.0,
+                height: 10.0,
+            });
+        }
+
+        index
+    }
+
+    #[test]
+    fn first_below() {
+        let index = index();
+
+        assert_eq!(index.first_below(-5.0), 0);
+        assert_eq!(index.first_below(5.0), 0);
+        assert_eq!(index.first_below(15.0), 1);
+        assert_eq!(index.first_below(22.0), 2);
+        assert_eq!(index.first_below(40.0), 3);
+        assert_eq!(LineIndex::default().first_below(0.0), 0);
+    }
+
+    #[test]
+    fn line_at() {
+        let index = index();
+
+        assert_eq!(index.line_at((50.0, 5.0)), Some(0));
+        assert_eq!(index.line_at((50.0, 30.0)), Some(2));
+        // in the gap, beside the lines and below them
+        assert_eq!(index.line_at((50.0, 22.0)), None);
+        assert_eq!(index.line_at((150.0, 5.0)), None);
+        assert_eq!(index.line_at((50.0, 40.0)), None);
+    }
+
+    #[test]
+    fn lines_between() {
+        let index = index();
+
+        assert_eq!(index.lines_between(0.0, 5.0), 0..1);
+        assert_eq!(index.lines_between(12.0, 26.0), 1..3);
+        assert_eq!(index.lines_between(21.0, 24.0), 2..2);
+        assert_eq!(index.lines_between(50.0, 60.0), 3..3);
+    }
+}
@@ -1,21 +1,54 @@
+mod accessible;
 mod config;
-mod lines;
-mod types;
-
-use anyhow::{Context as _, Result};
+mod export;
+mod find;
+mod highlight;
+mod hints;
+mod hit;
+pub mod lines;
+mod print;
+mod selection;
+mod tiles;
+pub mod types;
+
+use anyhow::{anyhow, Context as _, Result};
 use cairo::Context;
-use float_cmp::approx_eq;
-use lines::Line;
+use hints::Hint;
+use hit::{LineIndex, Rect};
+use lines::{LayoutKey, Line};
 use mime::Mime;
-use std::{collections::HashMap, ops::Deref};
-use types::{text_gemini::Gemini, text_plain::Plain};
+use pango::FontMapExt;
+use selection::{Position, Selection};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ops::Deref,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use tiles::{TileCache, TileKey, TILE_HEIGHT};
+use types::{image::ImageViewer, text_gemini::Gemini, text_markdown::Markdown, text_plain::Plain};
 use url::Url;
 
-pub use config::Theme;
+pub use accessible::{AccessibleLine, Role};
+pub use config::{Color, InlineImages, Theme};
+pub use export::ExportFormat;
+pub use find::{FindOptions, Match};
+pub use hit::Hit;
+pub use types::generic::{DecodedImage, INLINE_LIMITS};
+
+// how long render may spend laying out lines before drawing what it has
+const RENDER_LAYOUT_TIME: Duration = Duration::from_millis(8);
 
 pub enum Msg {
     Goto(String),
     Tooltip(String),
+
+    // the page needs to be drawn again
+    Redraw,
+    // text was selected with the mouse
+    Select(String),
 }
 
 pub struct Data {
@@ -27,18 +60,24 @@ pub struct Data {
     pub theme: Theme,
 }
 
-pub struct Cache {
-    pub source: String,
-    pub mime: Mime,
+impl Data {
+    // makes a url from the page absolute. links, tooltips and requests all
+    // go through here
+    pub fn resolve(&self, url: &str) -> Result<Url, url::ParseError> {
+        match &self.url {
+            Some(base) => base.join(url),
+            None => Url::parse(url),
+        }
+    }
+}
 
-    pub width: f64,
+pub struct Cache {
+    // viewport of the last render
     pub height: f64,
-
     pub y_offset: f64,
 
-    pub surface: Option<cairo::Surface>,
-
-    pub actual_height: i32,
+    pub tiles: TileCache,
+    pub dirty: bool,
 }
 
 pub struct Renderer {
@@ -46,14 +85,50 @@ pub struct Renderer {
 
     lines: Vec<Box<dyn Line>>,
 
+    // bumped whenever the page changes, so the cache doesn't need to compare
+    // the whole source. lines added at the end don't bump it, see lines_added
+    generation: u64,
+    theme_generation: u64,
+
+    // lines are laid out without a surface, using this
+    pango: pango::Context,
+    // what the lines are being laid out for, how many of them are placed and
+    // where the next one goes. page_height is estimated until all are placed
+    measured: Option<(LayoutKey, u64)>,
+    laid_out: usize,
+    layout_y: f64,
+    page_height: f64,
+    index: LineIndex,
+
     chunk_incomplete: String,
+    // body of pages shown by binary renderers, which have no source text
+    bytes: Vec<u8>,
 
-    renderers: HashMap<String, Box<dyn types::Renderer>>,
+    // images are scaled by this, see LayoutKey
+    zoom: f64,
+    // when the page finished loading, animations start from here
+    loaded: Instant,
+
+    // shared with the copies made by with_theme
+    renderers: Rc<RefCell<HashMap<String, Box<dyn types::Renderer>>>>,
     cache: Cache,
 
+    // decodes pages given as bytes, fallback_encoding is used when the MIME
+    // type doesn't name a known charset
     decoder: Option<encoding_rs::Decoder>,
+    fallback_encoding: &'static encoding_rs::Encoding,
 
     is_mouse_pressed: bool,
+    click_count: u32,
+    selection: Option<Selection>,
+
+    matches: Vec<Match>,
+    current_match: Option<usize>,
+
+    focus: Option<usize>,
+
+    hints: Vec<Hint>,
+    hint_input: String,
 }
 
 impl Renderer {
@@ -62,6 +137,13 @@ impl Renderer {
 
         renderers.insert("text/gemini".to_owned(), Box::new(Gemini::new()));
         renderers.insert("text/plain".to_owned(), Box::new(Plain::new()));
+        renderers.insert("text/markdown".to_owned(), Box::new(Markdown::new()));
+
+        for mime in &["image/png", "image/jpeg", "image/gif"] {
+            renderers.insert((*mime).to_owned(), Box::new(ImageViewer::new()));
+        }
+
+        let tile_budget = theme.tile_cache_size * 1024 * 1024;
 
         Self {
             data: Data {
@@ -74,51 +156,89 @@ impl Renderer {
             },
 
             cache: Cache {
-                source: String::new(),
-                mime: "text/plain".parse().unwrap(),
-
-                width: 0.0,
                 height: 0.0,
                 y_offset: 0.0,
 
-                surface: None,
-
-                actual_height: 0,
+                tiles: TileCache::new(tile_budget),
+                dirty: false,
             },
 
+            generation: 1,
+            theme_generation: 0,
+
+            pango: pangocairo::FontMap::get_default()
+                .and_then(|font_map| font_map.create_context())
+                .expect("Cannot create pango context"),
+            measured: None,
+            laid_out: 0,
+            layout_y: 0.0,
+            page_height: 0.0,
+            index: LineIndex::default(),
+
             lines: vec![],
             is_mouse_pressed: false,
+            click_count: 0,
+            selection: None,
+
+            matches: vec![],
+            current_match: None,
+
+            focus: None,
+
+            hints: vec![],
+            hint_input: String::new(),
 
             chunk_incomplete: String::new(),
+            bytes: vec![],
 
-            renderers,
+            zoom: 1.0,
+            loaded: Instant::now(),
+
+            renderers: Rc::new(RefCell::new(renderers)),
             decoder: None,
+            fallback_encoding: encoding_rs::UTF_8,
         }
     }
 
-    pub fn new_page_chunk(&mut self, raw_contents: &str) -> Result<()> {
-        if self.data.source.is_empty() {
-            self.lines.clear();
-        }
+    // adds bytes of the page, in the charset from the MIME type. sequences
+    // split between chunks are kept until the rest arrives
+    pub fn new_page_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let binary = find_renderer(&mut self.renderers.borrow_mut(), &self.data.mime)
+            .map_or(false, |renderer| renderer.is_binary());
 
-        // evil lifetime hacking
-        let mut contents = raw_contents.to_owned();
-        if let Some(decoder) = &mut self.decoder {
-            if let encoding_rs::DecoderResult::Malformed(_, _) = decoder
-                .decode_to_string_without_replacement(raw_contents.as_bytes(), &mut contents, false)
-                .0
-            {
-                return Err(anyhow::anyhow!("Malformed input"));
+        if binary {
+            if self.bytes.is_empty() {
+                self.begin_page();
             }
+            self.bytes.extend_from_slice(bytes);
+
+            self.lines.extend(
+                find_renderer(&mut self.renderers.borrow_mut(), &self.data.mime)
+                    .context("no renderer for mime")?
+                    .parse_bytes(bytes)
+                    .context("Cannot render page")?,
+            );
+
+            self.lines_added();
+            return Ok(());
+        }
+
+        let contents = self.decode(bytes, false);
+        self.new_page_chunk(&contents)
+    }
+
+    // adds already decoded text to the page
+    pub fn new_page_chunk(&mut self, contents: &str) -> Result<()> {
+        if self.data.source.is_empty() {
+            self.begin_page();
         }
 
         for chr in contents.chars() {
             if chr == '\n' {
                 let line = self.chunk_incomplete.clone();
 
-                self.lines.push(
-                    self.renderers
-                        .get_mut(self.data.mime.essence_str())
+                self.lines.extend(
+                    find_renderer(&mut self.renderers.borrow_mut(), &self.data.mime)
                         .context("no renderer for mime")?
                         .parse_line(&line)
                         .context("Cannot render line")?,
@@ -130,7 +250,78 @@ impl Renderer {
             }
         }
 
-        self.data.source += &contents;
+        self.data.source += contents;
+        self.lines_added();
+
+        Ok(())
+    }
+
+    // clears what's left of the previous page, before the first chunk of a new one
+    fn begin_page(&mut self) {
+        self.lines.clear();
+        self.selection = None;
+
+        self.matches.clear();
+        self.current_match = None;
+
+        self.focus = None;
+
+        self.hints.clear();
+        self.hint_input.clear();
+
+        if let Some(renderer) = find_renderer(&mut self.renderers.borrow_mut(), &self.data.mime) {
+            renderer.reset();
+        }
+
+        self.generation += 1;
+    }
+
+    // lines were only added after the others, so the layout goes on from the
+    // last placed line and only the tiles from there down are drawn again
+    fn lines_added(&mut self) {
+        match self.measured {
+            Some((key, _)) => self
+                .cache
+                .tiles
+                .retain_above(key.width as i32, self.layout_y),
+            None => self.cache.tiles.clear(),
+        }
+    }
+
+    pub fn finish_page(&mut self) -> Result<()> {
+        // whatever is left of a sequence cut off at the end of the page
+        if self.decoder.is_some() {
+            let rest = self.decode(&[], true);
+            self.decoder = None;
+
+            if !rest.is_empty() {
+                self.new_page_chunk(&rest)?;
+            }
+        }
+
+        if !self.chunk_incomplete.is_empty() {
+            self.new_page_chunk("\n")?;
+        }
+
+        {
+            let mut renderers = self.renderers.borrow_mut();
+            let renderer =
+                find_renderer(&mut renderers, &self.data.mime).context("no renderer for mime")?;
+
+            if !self.bytes.is_empty() {
+                self.lines.extend(
+                    renderer
+                        .finish_bytes(&self.bytes)
+                        .context("Cannot render page")?,
+                );
+            }
+
+            self.lines
+                .extend(renderer.finish().context("Cannot render line")?);
+        }
+
+        self.loaded = Instant::now();
+        self.lines_added();
         Ok(())
     }
 
@@ -138,16 +329,42 @@ impl Renderer {
         // we might want to assume this runs before any chunks are sent.
         log::debug!("renderer mime: {:?}", mime);
         self.data.mime = mime;
+        self.generation += 1;
 
-        self.decoder = if let Some(encoding) = self.data.mime.get_param(mime::CHARSET) {
-            if let Some(encoding) = encoding_rs::Encoding::for_label(encoding.as_str().as_bytes()) {
-                Some(encoding.new_decoder())
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        let encoding = charset_encoding(&self.data.mime, self.fallback_encoding);
+
+        // a BOM at the start of the page overrides this
+        self.decoder = Some(encoding.new_decoder());
+    }
+
+    // used for pages that don't say which charset they're in
+    pub fn set_fallback_encoding(&mut self, label: &str) -> Result<()> {
+        self.fallback_encoding = encoding_rs::Encoding::for_label(label.as_bytes())
+            .with_context(|| format!("Unknown encoding {}", label))?;
+
+        Ok(())
+    }
+
+    fn decode(&mut self, bytes: &[u8], last: bool) -> String {
+        let fallback = self.fallback_encoding;
+        let decoder = self.decoder.get_or_insert_with(|| fallback.new_decoder());
+
+        decode_chunk(decoder, bytes, last)
+    }
+
+    // adds support for a mime type, like "text/markdown", or every subtype of
+    // one, like "text/*". replaces what was registered for the same pattern,
+    // including the built in renderers
+    pub fn register(&mut self, mime_pattern: &str, renderer: Box<dyn types::Renderer>) {
+        self.renderers
+            .borrow_mut()
+            .insert(mime_pattern.to_ascii_lowercase(), renderer);
+    }
+
+    // whether pages of this mime type can be shown, otherwise they're better
+    // saved to a file
+    pub fn is_supported(&self, mime: &Mime) -> bool {
+        find_pattern(&self.renderers.borrow(), mime).is_some()
     }
 
     pub fn set_url(&mut self, url: Url) -> Result<()> {
@@ -160,6 +377,79 @@ impl Renderer {
     pub fn reset(&mut self) {
         self.data.mime = "text/plain".parse().unwrap();
         self.data.source = String::new();
+        self.decoder = None;
+        self.bytes.clear();
+        self.zoom = 1.0;
+        self.generation += 1;
+    }
+
+    pub fn get_zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    // scales images on the page, 1.0 fits them in the content width
+    pub fn set_zoom(&mut self, zoom: f64) {
+        let zoom = zoom.max(0.1).min(8.0);
+
+        if (zoom - self.zoom).abs() > f64::EPSILON {
+            self.zoom = zoom;
+            self.generation += 1;
+        }
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.set_zoom(self.zoom * 1.25);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.set_zoom(self.zoom / 1.25);
+    }
+
+    // moves animated lines, like gifs, to their current frame. returns how
+    // long until the next frame, None if nothing on the page moves
+    pub fn animate(&mut self) -> Option<Duration> {
+        let elapsed = self.loaded.elapsed();
+        let top = self.cache.y_offset;
+        let bottom = top + self.cache.height;
+
+        let mut next: Option<Duration> = None;
+
+        for i in 0..self.lines.len() {
+            let frame = match self.lines[i].animate(elapsed) {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            next = Some(next.map_or(frame, |next| next.min(frame)));
+
+            // frames are drawn into the tiles, only the ones on screen are
+            // drawn again. the others are by the next frame after scrolling
+            let (y, height) = (self.lines[i].get_pos().1, self.lines[i].get_size().1);
+            if y < bottom && y + height > top {
+                self.redraw_line(i);
+            }
+        }
+
+        next
+    }
+
+    // drops the tiles the line is drawn in, so they're drawn again
+    fn redraw_line(&mut self, line: usize) {
+        if let Some(line) = self.lines.get(line) {
+            let (y, height) = (line.get_pos().1, line.get_size().1);
+            self.cache.tiles.remove_between(y, y + height);
+        }
+    }
+
+    // lines lay themselves out again with the new theme
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.cache
+            .tiles
+            .set_budget(theme.tile_cache_size * 1024 * 1024);
+
+        self.data.theme = theme;
+        self.theme_generation += 1;
+        self.generation += 1;
     }
 
     pub fn render(
@@ -168,110 +458,797 @@ impl Renderer {
         height: f64,
         ctx: &impl Deref<Target = Context>,
     ) -> (i32, i32) {
-        //todo!("CACHE THE SURFACE"); // https://www.cairographics.org/manual/cairo-Image-Surfaces.html
+        let width = ctx.clip_extents().2;
 
-        let size = ctx.clip_extents();
+        // long pages are laid out in slices by the caller, see layout_step
+        let (page_height, done) = self.layout_step(width, RENDER_LAYOUT_TIME);
 
-        if !(approx_eq!(f64, self.cache.width, size.2)
-            && approx_eq!(f64, self.cache.height, height)
-            && approx_eq!(f64, self.cache.y_offset, y_offset)
-            && self.cache.source == self.data.source
-            && self.cache.mime == self.data.mime)
-        {
-            let surf = ctx
-                .get_target()
-                .create_similar_image(cairo::Format::ARgb32, size.2 as i32, size.3 as i32)
-                .expect("Cannot create cache surface");
+        if self.cache.dirty {
+            self.cache.tiles.clear();
+            self.cache.dirty = false;
+        }
 
-            self.cache.surface = Some(surf);
+        self.cache.y_offset = y_offset;
+        self.cache.height = height;
+
+        let first = (y_offset.max(0.0) / TILE_HEIGHT) as usize;
+        let last = ((y_offset + height).max(0.0) / TILE_HEIGHT) as usize;
+
+        for index in first..=last {
+            let key = TileKey {
+                index,
+                width: width as i32,
+                generation: self.generation,
+            };
+
+            // tiles with placeholders in them are drawn again once the
+            // layout catches up
+            if !done && key.top() + TILE_HEIGHT > self.layout_y {
+                let tile = self.draw_tile(key);
+                tiles::composite(ctx, &tile, key, width);
+                continue;
+            }
 
-            let surf = self.cache.surface.as_mut().unwrap();
-            let ctx = &cairo::Context::new(surf);
+            if self.cache.tiles.get(&key).is_none() {
+                let tile = self.draw_tile(key);
+                self.cache.tiles.insert(key, tile);
+            }
 
-            ctx.set_source_rgb(
-                self.data.theme.background_color.0 as f64 / 255.0,
-                self.data.theme.background_color.1 as f64 / 255.0,
-                self.data.theme.background_color.2 as f64 / 255.0,
+            if let Some(tile) = self.cache.tiles.get(&key) {
+                tiles::composite(ctx, tile, key, width);
+            }
+        }
+
+        (width as i32, page_height as i32)
+    }
+
+    fn draw_tile(&mut self, key: TileKey) -> cairo::ImageSurface {
+        let image =
+            cairo::ImageSurface::create(cairo::Format::ARgb32, key.width, TILE_HEIGHT as i32)
+                .expect("Cannot create tile surface");
+
+        let tile_ctx = cairo::Context::new(&image);
+        tile_ctx.translate(0.0, -key.top());
+
+        self.draw_page(&tile_ctx, key.top(), TILE_HEIGHT);
+
+        for hint in &self.hints {
+            if !hint.label.starts_with(&self.hint_input) {
+                continue;
+            }
+
+            let pos = self.lines[hint.line].get_pos();
+            hints::draw(
+                &tile_ctx,
+                &self.data.theme,
+                &hint.label,
+                self.hint_input.len(),
+                pos,
             );
+        }
+
+        image
+    }
 
-            ctx.paint();
+    // renders the whole page at the given width into a file, without needing
+    // a display. pdfs are split into pages like printing does
+    pub fn export(&mut self, width: f64, format: ExportFormat, path: &Path) -> Result<()> {
+        let result = match format {
+            ExportFormat::Pdf => {
+                let page_height = width * export::PAGE_RATIO;
+                let pages = self.paginate(width, page_height)?;
+
+                export::write_pdf((width, page_height), path, pages.len(), |ctx, page| {
+                    self.draw_print_page(ctx, width, pages[page]);
+                })
+            }
+            _ => {
+                let height = self.measure(width);
 
-            let pango = pangocairo::create_layout(ctx).expect("cannot create pango layout");
+                export::write(format, (width, height), path, |ctx| {
+                    self.draw_page(ctx, 0.0, f64::INFINITY);
+                })
+            }
+        };
 
-            let w = ctx.clip_extents().2;
-            let margin = self.data.theme.margin;
+        // lines were laid out for another width
+        self.cache.dirty = true;
 
-            ctx.move_to(margin, self.data.theme.paragraph_spacing * 2.0);
-            for line in &mut self.lines {
-                let pos = line.get_pos();
-                let size = line.get_size();
+        result
+    }
 
-                let curr = ctx.get_current_point();
+    // a copy of the current page, laid out with another theme. it's parsed
+    // with the same renderers, including the registered ones
+    pub fn with_theme(&self, theme: Theme) -> Result<Self> {
+        let mut renderer = Self::new(theme);
 
-                let fixed_width = w.min(self.data.theme.max_content_width) - (margin * 2.0);
-                let x = (w / 2.0) - (fixed_width / 2.0);
+        renderer.renderers = self.renderers.clone();
+        renderer.data.url = self.data.url.clone();
 
-                ctx.rel_move_to(-curr.0 + x, 0.0);
+        // not using set_mime, since the source is already decoded
+        renderer.data.mime = self.data.mime.clone();
 
-                // clip lines for performance, but include extra 2 lines as to make
-                // sure other lines load properly. could be smaller I assume, but
-                // let's play it safe
-                if pos.1 - (pos.1 * 2.0) <= y_offset + height && (pos.1 + size.1) * 2.0 >= y_offset
-                {
-                    line.draw(ctx, &pango, &self.data.theme);
-                }
+        if self.bytes.is_empty() {
+            renderer.new_page_chunk(&self.data.source)?;
+        } else {
+            renderer.new_page_bytes(&self.bytes)?;
+        }
+        renderer.finish_page()?;
+
+        // the copy finished the page, the renderer needs to be where this one
+        // left it if the rest of the page is still coming
+        if self.decoder.is_some() {
+            self.resume_renderer()?;
+        }
 
-                // this is required to be outside of the if to be able to figure out
-                // the rest of the page's size and send to the parent
-                ctx.rel_move_to(0.0, line.get_size().1 + self.data.theme.paragraph_spacing);
+        Ok(renderer)
+    }
+
+    // parses the page so far again, without keeping the lines
+    fn resume_renderer(&self) -> Result<()> {
+        let mut renderers = self.renderers.borrow_mut();
+        let renderer = match find_renderer(&mut renderers, &self.data.mime) {
+            Some(renderer) => renderer,
+            None => return Ok(()),
+        };
+
+        renderer.reset();
+
+        if !self.bytes.is_empty() {
+            renderer.parse_bytes(&self.bytes)?;
+            return Ok(());
+        }
+
+        // the last line isn't parsed until it's complete
+        let parsed = self.data.source.len() - self.chunk_incomplete.len();
+        for line in self.data.source[..parsed].split_terminator('\n') {
+            renderer.parse_line(line)?;
+        }
+
+        Ok(())
+    }
+
+    // text of the first heading
+    pub fn get_title(&self) -> Option<String> {
+        self.lines
+            .iter()
+            .find(|line| matches!(line.get_role(), Role::Heading(_)))
+            .and_then(|line| line.get_text())
+            .map(str::to_owned)
+    }
+
+    // splits the page into parts that fit in pages of the given size, without
+    // splitting lines unless they are taller than a page
+    pub fn paginate(&mut self, width: f64, page_height: f64) -> Result<Vec<(f64, f64)>> {
+        let page_height = page_height - print::HEADER_HEIGHT;
+
+        // nothing would fit below the header
+        if page_height <= 0.0 {
+            return Err(anyhow!(
+                "Pages are too short to print on, they need to be taller than {} pixels",
+                print::HEADER_HEIGHT
+            ));
+        }
+
+        let height = self.measure(width);
+
+        let mut pages = vec![];
+        let mut start = 0.0;
+
+        for line in &self.lines {
+            let (y, h) = (line.get_pos().1, line.get_size().1);
+
+            if y + h > start + page_height && y > start {
+                pages.push((start, y));
+                start = y;
             }
 
-            self.cache.width = size.2;
-            self.cache.height = height;
-            self.cache.source = self.data.source.clone();
-            self.cache.mime = self.data.mime.clone();
-            self.cache.y_offset = y_offset;
+            while y + h > start + page_height {
+                pages.push((start, start + page_height));
+                start += page_height;
+            }
+        }
 
-            self.cache.actual_height =
-                ctx.get_current_point().1 as i32 + self.data.theme.paragraph_spacing as i32 * 2;
+        if height > start || pages.is_empty() {
+            pages.push((start, height));
         }
 
-        let surf = self.cache.surface.as_mut().unwrap();
+        self.cache.dirty = true;
+        Ok(pages)
+    }
+
+    // draws a part of the page returned by paginate, below the print header
+    pub fn draw_print_page(&mut self, ctx: &Context, width: f64, (start, end): (f64, f64)) {
+        self.measure(width);
+
+        let title = self.get_title();
+        let url = self.data.url.as_ref().map(Url::to_string);
+
+        print::draw_header(
+            ctx,
+            &self.data.theme,
+            title.as_deref(),
+            url.as_deref(),
+            width,
+        );
+
+        ctx.save();
+
+        ctx.translate(0.0, print::HEADER_HEIGHT);
+        ctx.rectangle(0.0, 0.0, width, end - start);
+        ctx.clip();
+        ctx.translate(0.0, -start);
+
+        self.draw_page(ctx, start, end - start);
+
+        ctx.restore();
+    }
+
+    // images lines on screen want loaded, like inline previews, as (line,
+    // url) with the url made absolute. each is returned once, and the result
+    // goes to set_content
+    pub fn take_requests(&mut self) -> Vec<(usize, String)> {
+        let top = self.cache.y_offset;
+        let mut requests = vec![];
+
+        for i in self.index.lines_between(top, top + self.cache.height) {
+            let line = &mut self.lines[i];
+            let url = match line.take_request() {
+                Some(url) => url,
+                None => continue,
+            };
+
+            match self.data.resolve(&url) {
+                Ok(url) => requests.push((i, url.to_string())),
+                Err(e) => line.set_content(Err(anyhow!(e).context("Cannot parse URL"))),
+            }
+        }
+
+        requests
+    }
+
+    // gives a line what it asked for with take_requests, decoded with
+    // DecodedImage::decode and INLINE_LIMITS off the main thread. the line
+    // can change size, so the page is laid out again
+    pub fn set_content(&mut self, line: usize, content: Result<DecodedImage>) {
+        if let Some(line) = self.lines.get_mut(line) {
+            line.set_content(content);
+
+            // which also draws every tile again
+            self.generation += 1;
+        }
+    }
+
+    // lays out and places every line for the given width, returns the height
+    // of the whole page. only lines that changed are laid out again
+    pub fn measure(&mut self, width: f64) -> f64 {
+        self.layout(width, None);
+        self.page_height
+    }
+
+    // like measure, but stops after the given time so long pages don't block
+    // the main loop. returns the page height, estimated from the lines placed
+    // so far, and whether every line is placed
+    pub fn layout_step(&mut self, width: f64, time: Duration) -> (f64, bool) {
+        let done = self.layout(width, Some(Instant::now() + time));
+        (self.page_height, done)
+    }
+
+    // whether every line is placed for the last width
+    pub fn is_laid_out(&self) -> bool {
+        self.measured.is_some() && self.laid_out == self.lines.len()
+    }
+
+    fn layout(&mut self, width: f64, deadline: Option<Instant>) -> bool {
+        let key = LayoutKey {
+            width,
+            theme: self.theme_generation,
+            zoom: self.zoom,
+        };
+
+        let theme = &self.data.theme;
+
+        let fixed_width = width.min(theme.max_content_width) - (theme.margin * 2.0);
+        let x = (width / 2.0) - (fixed_width / 2.0);
+        let top = theme.paragraph_spacing * 2.0;
+
+        if self.measured != Some((key, self.generation)) {
+            self.measured = Some((key, self.generation));
+            self.laid_out = 0;
+            self.layout_y = top;
+            self.index.clear();
+        }
+
+        while let Some(line) = self.lines.get_mut(self.laid_out) {
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                break;
+            }
+
+            line.measure(&self.pango, theme, key);
+            line.set_pos((x, self.layout_y));
+
+            let (width, height) = line.get_size();
+            self.index.push(Rect {
+                x,
+                y: self.layout_y,
+                width,
+                height,
+            });
+
+            self.layout_y += height + theme.paragraph_spacing;
+            self.laid_out += 1;
+        }
+
+        let remaining = self.lines.len() - self.laid_out;
+        let average = if self.laid_out > 0 {
+            (self.layout_y - top) / self.laid_out as f64
+        } else {
+            theme.content.size + theme.paragraph_spacing
+        };
+
+        self.page_height = self.layout_y + (remaining as f64 * average) + top;
+
+        remaining == 0
+    }
+
+    // paints the background and the lines placed so far in the given area,
+    // with a placeholder below them if the layout isn't done yet
+    fn draw_page(&mut self, ctx: &Context, y_offset: f64, height: f64) {
+        ctx.set_source_rgb(
+            self.data.theme.background_color.0 as f64 / 255.0,
+            self.data.theme.background_color.1 as f64 / 255.0,
+            self.data.theme.background_color.2 as f64 / 255.0,
+        );
 
-        ctx.set_source_surface(surf, 0.0, 0.0);
         ctx.paint();
 
-        (size.2 as i32, self.cache.actual_height)
+        for i in self.index.lines_between(y_offset, y_offset + height) {
+            self.lines[i].draw(ctx, &self.data.theme);
+        }
+
+        if self.laid_out < self.lines.len() {
+            self.draw_placeholder(ctx, y_offset.max(self.layout_y), y_offset + height);
+        }
+    }
+
+    // grey bars where lines that aren't laid out yet will go
+    fn draw_placeholder(&self, ctx: &Context, top: f64, bottom: f64) {
+        let theme = &self.data.theme;
+        let width = ctx.clip_extents().2;
+
+        let fixed_width = width.min(theme.max_content_width) - (theme.margin * 2.0);
+        let x = (width / 2.0) - (fixed_width / 2.0);
+
+        let bar = theme.content.size;
+        let step = bar + theme.content.line_spacing + theme.paragraph_spacing;
+
+        ctx.set_source_rgba(
+            theme.content.color.0 as f64 / 255.0,
+            theme.content.color.1 as f64 / 255.0,
+            theme.content.color.2 as f64 / 255.0,
+            0.1,
+        );
+
+        // start on the same grid for every tile
+        let mut y = self.layout_y + ((top - self.layout_y) / step).floor() * step;
+        while y < bottom {
+            ctx.rectangle(x, y, fixed_width, bar);
+            y += step;
+        }
+
+        ctx.fill();
+    }
+
+    pub fn get_selection(&self) -> Option<String> {
+        let selection = self.selection.filter(|s| !s.is_empty())?;
+        let ((start, _), (end, _)) = selection.range();
+
+        let mut text = vec![];
+        for (i, line) in self.lines.iter().enumerate().take(end + 1).skip(start) {
+            if let Some(line_text) = line.get_text() {
+                if let Some((from, to)) = selection.range_for(i, line_text.len()) {
+                    text.push(&line_text[from..to]);
+                }
+            }
+        }
+
+        Some(text.join("\n"))
+    }
+
+    pub fn select_all(&mut self) {
+        let last = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| line.get_text().map(|text| (i, text.len())))
+            .last();
+
+        self.selection = last.map(|last| Selection::new((0, 0), last));
+        self.update_selection();
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection = None;
+        self.update_selection();
+    }
+
+    fn update_selection(&mut self) {
+        for (i, line) in self.lines.iter_mut().enumerate() {
+            let len = line.get_text().map(str::len);
+
+            line.set_selection(match (&self.selection, len) {
+                (Some(selection), Some(len)) => selection.range_for(i, len),
+                _ => None,
+            });
+        }
+
+        self.cache.dirty = true;
+    }
+
+    // what's under pos, resolved to a character in the line when possible
+    pub fn hit_test(&self, pos: (f64, f64)) -> Option<Hit> {
+        let line = self.index.line_at(pos)?;
+        let hit = self.lines.get(line)?;
+
+        Some(Hit {
+            line,
+            index: hit.get_index(pos),
+            link: hit
+                .get_link_at(pos)
+                .and_then(|url| self.data.resolve(url).ok())
+                .map(|url| url.to_string()),
+        })
+    }
+
+    // the text position closest to pos, used for selection
+    fn get_position(&self, pos: (f64, f64)) -> Option<Position> {
+        let first = self.index.first_below(pos.1);
+
+        // the first line with text at or below pos
+        let below = self.lines[first.min(self.lines.len())..]
+            .iter()
+            .enumerate()
+            .find(|(_, line)| line.get_text().is_some());
+
+        if let Some((i, line)) = below {
+            return line.get_index(pos).map(|index| (first + i, index));
+        }
+
+        // below every line, select until the end
+        self.lines
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, line)| line.get_text().map(|text| (i, text.len())))
+    }
+
+    pub fn find(&mut self, query: &str, options: FindOptions) -> Vec<Match> {
+        self.matches = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| line.get_text().map(|text| (i, text)))
+            .flat_map(|(i, text)| {
+                find::find_in(text, query, options)
+                    .into_iter()
+                    .map(move |(start, end)| Match {
+                        line: i,
+                        start,
+                        end,
+                    })
+            })
+            .collect();
+
+        self.current_match = None;
+        self.update_matches();
+
+        self.matches.clone()
+    }
+
+    pub fn clear_find(&mut self) {
+        self.matches.clear();
+        self.current_match = None;
+        self.update_matches();
+    }
+
+    // (index of the current match, amount of matches)
+    pub fn get_find_status(&self) -> (Option<usize>, usize) {
+        (self.current_match, self.matches.len())
+    }
+
+    // moves to the next match, returns its position in page coordinates to
+    // scroll to
+    pub fn find_next(&mut self) -> Option<(f64, f64)> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        self.current_match = Some(match self.current_match {
+            Some(i) => (i + 1) % self.matches.len(),
+            None => 0,
+        });
+
+        self.update_matches();
+        self.get_current_match_pos()
+    }
+
+    pub fn find_previous(&mut self) -> Option<(f64, f64)> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        self.current_match = Some(match self.current_match {
+            Some(0) | None => self.matches.len() - 1,
+            Some(i) => i - 1,
+        });
+
+        self.update_matches();
+        self.get_current_match_pos()
+    }
+
+    fn get_current_match_pos(&self) -> Option<(f64, f64)> {
+        let found = self.matches[self.current_match?];
+        let line = &self.lines[found.line];
+
+        line.get_index_pos(found.start)
+            .or_else(|| Some(line.get_pos()))
+    }
+
+    fn update_matches(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.set_matches(vec![], None);
+        }
+
+        // matches are sorted by line, give each line its own group of them
+        let mut start = 0;
+        while start < self.matches.len() {
+            let line = self.matches[start].line;
+            let end = start
+                + self.matches[start..]
+                    .iter()
+                    .take_while(|found| found.line == line)
+                    .count();
+
+            let current = self
+                .current_match
+                .filter(|current| (start..end).contains(current))
+                .map(|current| current - start);
+
+            self.lines[line].set_matches(
+                self.matches[start..end]
+                    .iter()
+                    .map(|found| (found.start, found.end))
+                    .collect(),
+                current,
+            );
+
+            start = end;
+        }
+
+        self.cache.dirty = true;
+    }
+
+    pub fn get_focus(&self) -> Option<usize> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: Option<usize>) {
+        if let Some(line) = self.focus.and_then(|i| self.lines.get_mut(i)) {
+            line.set_focused(false);
+        }
+
+        if let Some(line) = focus.and_then(|i| self.lines.get_mut(i)) {
+            line.set_focused(true);
+        }
+
+        self.focus = focus;
+        self.cache.dirty = true;
+    }
+
+    // moves the focus to the next focusable line, returns its rectangle in
+    // page coordinates to scroll to, or None after the last one
+    pub fn focus_next(&mut self) -> Option<(f64, f64, f64, f64)> {
+        self.move_focus(true)
+    }
+
+    pub fn focus_previous(&mut self) -> Option<(f64, f64, f64, f64)> {
+        self.move_focus(false)
+    }
+
+    fn move_focus(&mut self, forwards: bool) -> Option<(f64, f64, f64, f64)> {
+        let focusable: Vec<usize> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.is_focusable())
+            .map(|(i, _)| i)
+            .collect();
+
+        // past either end the focus is cleared, so it can leave the page
+        let next = match self.focus {
+            Some(current) if forwards => focusable.iter().find(|i| **i > current),
+            Some(current) => focusable.iter().rev().find(|i| **i < current),
+            None if forwards => focusable.first(),
+            None => focusable.last(),
+        }
+        .copied();
+
+        self.set_focus(next);
+
+        let line = &self.lines[next?];
+        let (pos, size) = (line.get_pos(), line.get_size());
+
+        Some((pos.0, pos.1, size.0, size.1))
+    }
+
+    pub fn get_focus_tooltip(&self) -> Option<String> {
+        self.lines[self.focus?].get_tooltip(&self.data)
+    }
+
+    // same as clicking the focused line
+    pub fn activate_focus(&mut self) -> Option<Msg> {
+        let focus = self.focus?;
+        self.lines[focus].click(&self.data)
+    }
+
+    // index and rectangle in page coordinates of every link in the area drawn
+    // by the last render
+    pub fn get_visible_links(&self) -> Vec<(usize, (f64, f64, f64, f64))> {
+        let top = self.cache.y_offset;
+        let bottom = top + self.cache.height;
+
+        self.index
+            .lines_between(top, bottom)
+            .filter_map(|i| Some((i, self.lines.get(i)?)))
+            .filter(|(_, line)| line.is_focusable())
+            .map(|(i, line)| {
+                let (pos, size) = (line.get_pos(), line.get_size());
+                (i, (pos.0, pos.1, size.0, size.1))
+            })
+            .collect()
+    }
+
+    // lines from the index on, so a tree can be kept up to date while the
+    // page loads
+    pub fn get_accessible_lines(&self, from: usize) -> Vec<AccessibleLine> {
+        self.lines
+            .iter()
+            .skip(from)
+            .map(|line| AccessibleLine {
+                role: line.get_role(),
+                text: line.get_text().unwrap_or_default().to_owned(),
+                description: line.get_tooltip(&self.data),
+                focusable: line.is_focusable(),
+            })
+            .collect()
+    }
+
+    pub fn is_showing_hints(&self) -> bool {
+        !self.hints.is_empty()
+    }
+
+    // labels every visible link, returns false if there aren't any
+    pub fn show_hints(&mut self) -> bool {
+        let links = self.get_visible_links();
+        let labels = hints::labels(links.len());
+
+        self.hints = links
+            .into_iter()
+            .zip(labels)
+            .map(|((line, _), label)| Hint { label, line })
+            .collect();
+
+        self.hint_input.clear();
+        self.cache.dirty = true;
+
+        self.is_showing_hints()
+    }
+
+    pub fn hide_hints(&mut self) {
+        self.hints.clear();
+        self.hint_input.clear();
+        self.cache.dirty = true;
+    }
+
+    // types a character of a hint label. once a label is typed fully, the
+    // hints are hidden and its link is clicked
+    pub fn hint_input(&mut self, chr: char) -> Option<Msg> {
+        self.hint_input.push(chr.to_ascii_lowercase());
+        self.cache.dirty = true;
+
+        let input = &self.hint_input;
+        let line = self
+            .hints
+            .iter()
+            .find(|hint| hint.label == *input)
+            .map(|hint| hint.line);
+
+        if let Some(line) = line {
+            self.hide_hints();
+            return self.lines[line].click(&self.data);
+        }
+
+        if !self.hints.iter().any(|hint| hint.label.starts_with(input)) {
+            self.hide_hints();
+        }
+
+        Some(Msg::Redraw)
+    }
+
+    pub fn hint_backspace(&mut self) {
+        self.hint_input.pop();
+        self.cache.dirty = true;
     }
 
     pub fn on_mouse_move(&mut self, pos: (f64, f64)) -> Option<Msg> {
         if self.is_mouse_pressed {
-            // todo: text selection
-            // but i don't know how i would figure out which character the cursor
-            // is under
-        } else {
-            for line in self.lines.iter() {
-                let coords = line.get_pos();
-                let size = line.get_size();
-
-                if pos.0 >= coords.0
-                    && pos.0 <= coords.0 + size.0
-                    && pos.1 >= coords.1
-                    && pos.1 <= coords.1 + size.1
-                {
-                    if let Some(tooltip) = line.get_tooltip(&self.data) {
-                        return Some(Msg::Tooltip(tooltip));
-                    }
+            if self.click_count > 1 {
+                return None;
+            }
+
+            let cursor = self.get_position(pos);
+
+            if let (Some(selection), Some(cursor)) = (&mut self.selection, cursor) {
+                if selection.cursor != cursor {
+                    selection.cursor = cursor;
+
+                    self.update_selection();
+                    return Some(Msg::Redraw);
                 }
             }
+        } else if let Some(line) = self.index.line_at(pos).and_then(|i| self.lines.get(i)) {
+            // links in text show where they lead, like link lines do
+            if let Some(url) = line.get_link_at(pos) {
+                let url = match self.data.resolve(url) {
+                    Ok(url) => url.to_string(),
+                    Err(_) => url.to_owned(),
+                };
+
+                return Some(Msg::Tooltip(url));
+            }
+
+            return line.get_tooltip(&self.data).map(Msg::Tooltip);
         }
 
         None
     }
 
-    pub fn on_mouse_press(&mut self, _pos: (f64, f64)) -> Option<Msg> {
+    // returns true if the page needs to be redrawn
+    pub fn on_scroll(&mut self, pos: (f64, f64), dx: f64) -> bool {
+        let line = self.index.line_at(pos).and_then(|i| self.lines.get_mut(i));
+
+        if let Some(line) = line {
+            if line.scroll(dx) {
+                self.cache.dirty = true;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // clicks is 2 for double clicks, 3 for triple clicks
+    pub fn on_mouse_press(&mut self, pos: (f64, f64), clicks: u32) -> Option<Msg> {
         self.is_mouse_pressed = true;
-        None
+        self.click_count = clicks;
+
+        let position = self.get_position(pos);
+
+        let lines = &self.lines;
+        let text = position.and_then(|(line, _)| lines[line].get_text());
+
+        self.selection = match (position, text, clicks) {
+            (Some((line, index)), Some(text), 2) => {
+                let (start, end) = selection::word_at(text, index);
+                Some(Selection::new((line, start), (line, end)))
+            }
+            (Some((line, _)), Some(text), 3) => Some(Selection::new((line, 0), (line, text.len()))),
+            (Some(position), _, _) => Some(Selection::new(position, position)),
+            _ => None,
+        };
+
+        self.update_selection();
+        Some(Msg::Redraw)
     }
 
     pub fn on_mouse_release(&mut self, pos: (f64, f64)) -> Option<Msg> {
@@ -279,19 +1256,307 @@ impl Renderer {
 
         self.is_mouse_pressed = false;
 
-        for line in self.lines.iter_mut() {
-            let coords = line.get_pos();
-            let size = line.get_size();
-
-            if pos.0 >= coords.0
-                && pos.0 <= coords.0 + size.0
-                && pos.1 >= coords.1
-                && pos.1 <= coords.1 + size.1
-            {
-                return line.click(&self.data);
-            }
+        if let Some(text) = self.get_selection() {
+            return Some(Msg::Select(text));
         }
 
-        None
+        let line = self.index.line_at(pos)?;
+        let msg = self.lines.get_mut(line)?.click_at(&self.data, pos);
+
+        // the line looks different now, like a preview that's loading
+        if let Some(Msg::Redraw) = msg {
+            self.redraw_line(line);
+        }
+
+        msg
+    }
+}
+
+// exact patterns win over "type/*", which wins over "*/*"
+fn find_pattern(
+    renderers: &HashMap<String, Box<dyn types::Renderer>>,
+    mime: &Mime,
+) -> Option<String> {
+    let patterns = [
+        mime.essence_str().to_owned(),
+        format!("{}/*", mime.type_()),
+        "*/*".to_owned(),
+    ];
+
+    patterns
+        .iter()
+        .find(|pattern| renderers.contains_key(pattern.as_str()))
+        .cloned()
+}
+
+fn find_renderer<'a>(
+    renderers: &'a mut HashMap<String, Box<dyn types::Renderer>>,
+    mime: &Mime,
+) -> Option<&'a mut Box<dyn types::Renderer>> {
+    let pattern = find_pattern(renderers, mime)?;
+    renderers.get_mut(&pattern)
+}
+
+// the encoding named by the charset parameter, or the fallback if there's
+// none or it's unknown
+fn charset_encoding(
+    mime: &Mime,
+    fallback: &'static encoding_rs::Encoding,
+) -> &'static encoding_rs::Encoding {
+    mime.get_param(mime::CHARSET)
+        .and_then(|charset| encoding_rs::Encoding::for_label(charset.as_str().as_bytes()))
+        .unwrap_or(fallback)
+}
+
+// decodes a chunk of the page. the decoder keeps sequences split between
+// chunks until the rest arrives, or the last chunk ends them
+fn decode_chunk(decoder: &mut encoding_rs::Decoder, bytes: &[u8], last: bool) -> String {
+    let capacity = decoder
+        .max_utf8_buffer_length(bytes.len())
+        .unwrap_or_default();
+
+    // malformed sequences turn into U+FFFD
+    let mut contents = String::with_capacity(capacity);
+    decoder.decode_to_string(bytes, &mut contents, last);
+
+    contents
+}
+
+// sizes of files and downloads, for people to read
+pub fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KB");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(1_048_576), "1.0 MB");
+        assert_eq!(format_size(5 * 1_048_576 / 2), "2.5 MB");
+    }
+
+    #[test]
+    fn resolve() {
+        let mut data = Data {
+            mime: mime::TEXT_PLAIN,
+            url: None,
+            source: String::new(),
+            theme: Theme::default(),
+        };
+
+        let resolve = |data: &Data, url| data.resolve(url).ok().map(|url| url.to_string());
+
+        // without a page url, only absolute urls work
+        assert_eq!(resolve(&data, "dir/page.gmi"), None);
+        assert_eq!(
+            resolve(&data, "gemini://host/page.gmi").as_deref(),
+            Some("gemini://host/page.gmi")
+        );
+
+        data.url = Some(Url::parse("gemini://host/dir/index.gmi").unwrap());
+
+        let cases = [
+            ("page.gmi", "gemini://host/dir/page.gmi"),
+            ("../up.gmi", "gemini://host/up.gmi"),
+            ("/root.gmi", "gemini://host/root.gmi"),
+            ("//other/", "gemini://other/"),
+            ("?query", "gemini://host/dir/index.gmi?query"),
+            ("https://example.com/", "https://example.com/"),
+        ];
+
+        for (url, expected) in cases.iter() {
+            assert_eq!(resolve(&data, url).as_deref(), Some(*expected), "{}", url);
+        }
+    }
+
+    fn decode_all(mime: &str, chunks: &[&[u8]]) -> String {
+        let mime: Mime = mime.parse().unwrap();
+        let mut decoder = charset_encoding(&mime, encoding_rs::UTF_8).new_decoder();
+
+        let mut text: String = chunks
+            .iter()
+            .map(|chunk| decode_chunk(&mut decoder, chunk, false))
+            .collect();
+
+        text += &decode_chunk(&mut decoder, &[], true);
+        text
+    }
+
+    #[test]
+    fn pattern_order() {
+        let mut renderers: HashMap<String, Box<dyn types::Renderer>> = HashMap::new();
+        assert_eq!(find_pattern(&renderers, &mime::TEXT_HTML), None);
+
+        for registered in &["*/*", "text/*", "text/plain"] {
+            renderers.insert((*registered).to_owned(), Box::new(Plain::new()));
+        }
+
+        let cases = [
+            ("text/plain", "text/plain"),
+            ("text/plain; charset=utf-8", "text/plain"),
+            ("text/html", "text/*"),
+            ("image/png", "*/*"),
+        ];
+
+        for (mime, expected) in cases.iter() {
+            let found = find_pattern(&renderers, &mime.parse().unwrap());
+            assert_eq!(found.as_deref(), Some(*expected), "{}", mime);
+        }
+    }
+
+    #[test]
+    fn charset() {
+        let cases: &[(&str, &[&[u8]], &str)] = &[
+            ("text/gemini", &[b"plain"], "plain"),
+            // "é" split between chunks
+            ("text/gemini", &[b"caf\xc3", b"\xa9"], "café"),
+            ("text/gemini; charset=utf-8", &[b"\xe2\x82", b"\xac"], "€"),
+            ("text/gemini; charset=iso-8859-1", &[b"caf\xe9"], "café"),
+            (
+                "text/gemini; charset=windows-1251",
+                &[b"\xcf\xf0\xe8"],
+                "При",
+            ),
+            (
+                "text/gemini; charset=shift_jis",
+                &[b"\x93\xfa", b"\x96{"],
+                "日本",
+            ),
+            // unknown charsets use the fallback
+            ("text/gemini; charset=unknown", &[b"caf\xc3\xa9"], "café"),
+            // a BOM wins over the charset
+            (
+                "text/gemini; charset=iso-8859-1",
+                &[b"\xef\xbb", b"\xbfcaf\xc3\xa9"],
+                "café",
+            ),
+            // cut off at the end of the page
+            ("text/gemini", &[b"caf\xc3"], "caf\u{fffd}"),
+        ];
+
+        for (mime, chunks, expected) in cases {
+            assert_eq!(decode_all(mime, chunks), *expected, "{} {:?}", mime, chunks);
+        }
+    }
+
+    // a plain text page with the given number of lines
+    fn text_page(lines: usize) -> Renderer {
+        let mut renderer = Renderer::new(Theme::default());
+        renderer.set_mime(mime::TEXT_PLAIN);
+        renderer.new_page_chunk(&"line\n".repeat(lines)).unwrap();
+        renderer.finish_page().unwrap();
+        renderer
+    }
+
+    fn export_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("moonrender-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn export_png() {
+        let path = export_path("page.png");
+
+        text_page(10)
+            .export(800.0, ExportFormat::Png, &path)
+            .unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(b"\x89PNG"));
+        std::fs::remove_file(&path).unwrap();
+
+        // cairo can't make an image this tall
+        let error = text_page(5000)
+            .export(800.0, ExportFormat::Png, &path)
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Page too tall for PNG"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn export_pdf() {
+        let path = export_path("page.pdf");
+        let mut page = text_page(5000);
+
+        page.export(800.0, ExportFormat::Pdf, &path).unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(b"%PDF"));
+        std::fs::remove_file(&path).unwrap();
+
+        // the same pages as the export, each fits below the header
+        let page_height = 800.0 * export::PAGE_RATIO;
+        let pages = page.paginate(800.0, page_height).unwrap();
+        assert!(pages.len() > 1);
+
+        for (start, end) in &pages {
+            assert!(end - start <= page_height - print::HEADER_HEIGHT);
+        }
+        assert_eq!(pages.first().unwrap().0, 0.0);
+        assert_eq!(pages.last().unwrap().1, page.measure(800.0));
+    }
+
+    #[test]
+    fn paginate() {
+        let mut page = text_page(10);
+        let height = page.measure(800.0);
+
+        assert_eq!(page.paginate(800.0, 10_000.0).unwrap(), [(0.0, height)]);
+
+        // no room left below the header
+        assert!(page.paginate(800.0, print::HEADER_HEIGHT).is_err());
+        assert!(page.paginate(800.0, 0.0).is_err());
+        assert!(page.paginate(800.0, -1.0).is_err());
+
+        // lines taller than a page are cut where the page ends
+        let pages = page.paginate(800.0, print::HEADER_HEIGHT + 1.0).unwrap();
+        assert!(pages.len() as f64 >= height - 1.0);
+        assert_eq!(pages.last().unwrap().1, height);
+    }
+
+    struct Shouting;
+
+    impl types::Renderer for Shouting {
+        fn parse_line(&mut self, line: &str) -> Result<Vec<Box<dyn Line>>> {
+            Ok(vec![Box::new(types::generic::Text::new(
+                line.to_uppercase(),
+            ))])
+        }
+    }
+
+    fn page_text(page: &Renderer) -> Vec<&str> {
+        page.lines
+            .iter()
+            .filter_map(|line| line.get_text())
+            .collect()
+    }
+
+    #[test]
+    fn with_theme() {
+        let mut page = Renderer::new(Theme::default());
+        page.register("text/x-shouting", Box::new(Shouting));
+        page.set_mime("text/x-shouting".parse().unwrap());
+        page.new_page_bytes(b"hello\nworld\n").unwrap();
+        page.finish_page().unwrap();
+
+        let copy = page.with_theme(Theme::default()).unwrap();
+        assert_eq!(page_text(&copy), ["HELLO", "WORLD"]);
+
+        // copied while loading, the rest of the page goes on from where it was
+        let mut page = Renderer::new(Theme::default());
+        page.set_mime("text/gemini".parse().unwrap());
+        page.new_page_bytes(b"```\n# not a heading\n").unwrap();
+
+        let copy = page.with_theme(Theme::default()).unwrap();
+        assert_eq!(copy.get_title(), None);
+
+        page.new_page_bytes(b"# still not\n```\n# Title\n").unwrap();
+        page.finish_page().unwrap();
+        assert_eq!(page.get_title().as_deref(), Some("Title"));
     }
 }
@@ -1,11 +1,63 @@
+use crate::accessible::Role;
+use crate::config::Color;
+use crate::types::generic::DecodedImage;
+use anyhow::Result;
 use cairo::Context;
-use pango::Layout;
+use std::time::Duration;
 
+// everything a line's layout depends on other than its own contents, lines
+// only lay themselves out again when this changes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LayoutKey {
+    // width of the whole drawing area
+    pub width: f64,
+    pub theme: u64,
+    // images are scaled by this, 1.0 fits them in the page
+    pub zoom: f64,
+}
+
+// how a range of a line's text is drawn, on top of the line's own style
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Span {
+    // byte range of the line's text
+    pub start: usize,
+    pub end: usize,
+
+    pub bold: bool,
+    pub italic: bool,
+    pub monospace: bool,
+    pub strikethrough: bool,
+    pub color: Option<Color>,
+
+    // spans with a target are drawn like links, and clicking them follows it
+    pub link: Option<String>,
+}
+
+impl Span {
+    pub fn contains(&self, index: usize) -> bool {
+        self.start <= index && index < self.end
+    }
+}
+
+// a piece of a page, like a paragraph or a link. renderers for new mime types
+// can implement this, or reuse the lines in types::generic
 pub trait Line {
     fn get_pos(&self) -> (f64, f64);
     fn get_size(&self) -> (f64, f64);
 
-    fn draw(&mut self, ctx: &Context, pango: &Layout, theme: &super::Theme);
+    // the renderer places lines in the page after measuring them
+    fn set_pos(&mut self, pos: (f64, f64));
+
+    // lays the line out, after which get_size is accurate. doesn't need a
+    // surface to draw on
+    fn measure(&mut self, pango: &pango::Context, theme: &super::Theme, key: LayoutKey);
+
+    // paints the measured line at its position
+    fn draw(&mut self, ctx: &Context, theme: &super::Theme);
+
+    fn get_role(&self) -> Role {
+        Role::Text // implementation optional
+    }
 
     fn get_tooltip(&self, _data: &super::Data) -> Option<String> {
         None // implementation optional
@@ -14,4 +66,74 @@ pub trait Line {
     fn click(&mut self, _data: &super::Data) -> Option<super::Msg> {
         None // implementation optional
     }
+
+    // target of the link under pos, in page coordinates, for link lines and
+    // lines with links in their text
+    fn get_link_at(&self, _pos: (f64, f64)) -> Option<&str> {
+        None // implementation optional
+    }
+
+    // clicks on a point of the line, in page coordinates. lines with more
+    // than one thing to click tell them apart here, others are just clicked
+    fn click_at(&mut self, data: &super::Data, _pos: (f64, f64)) -> Option<super::Msg> {
+        self.click(data) // implementation optional
+    }
+
+    // returns true if the line needs to be redrawn
+    fn scroll(&mut self, _dx: f64) -> bool {
+        false // implementation optional
+    }
+
+    // lines without text can't be selected
+    fn get_text(&self) -> Option<&str> {
+        None // implementation optional
+    }
+
+    // byte index of get_text() closest to pos, which is in page coordinates
+    fn get_index(&self, _pos: (f64, f64)) -> Option<usize> {
+        None // implementation optional
+    }
+
+    // byte range of get_text() to highlight as selected
+    fn set_selection(&mut self, _selection: Option<(usize, usize)>) {
+        // implementation optional
+    }
+
+    // byte ranges of get_text() that matched a search, and which one of them
+    // is the current match
+    fn set_matches(&mut self, _matches: Vec<(usize, usize)>, _current: Option<usize>) {
+        // implementation optional
+    }
+
+    // lines that can be focused with the keyboard, like links
+    fn is_focusable(&self) -> bool {
+        false // implementation optional
+    }
+
+    fn set_focused(&mut self, _focused: bool) {
+        // implementation optional
+    }
+
+    // position of the byte index of get_text(), in page coordinates
+    fn get_index_pos(&self, _index: usize) -> Option<(f64, f64)> {
+        None // implementation optional
+    }
+
+    // animated lines switch to their frame for the time since the page
+    // loaded, and return how long that frame stays
+    fn animate(&mut self, _elapsed: Duration) -> Option<Duration> {
+        None // implementation optional
+    }
+
+    // url of an image the line shows once it's loaded, like an inline
+    // preview. only asked while the line is on screen, and returned once
+    fn take_request(&mut self) -> Option<String> {
+        None // implementation optional
+    }
+
+    // the image take_request asked for, decoded with INLINE_LIMITS, or why
+    // it couldn't be loaded
+    fn set_content(&mut self, _content: Result<DecodedImage>) {
+        // implementation optional
+    }
 }
@@ -0,0 +1,48 @@
+use crate::Theme;
+use cairo::Context;
+use pango::{Alignment, EllipsizeMode};
+
+// space above every printed page for the header, in pixels
+pub const HEADER_HEIGHT: f64 = 32.0;
+
+// the page title on the left and its url on the right, above a thin rule
+pub fn draw_header(
+    ctx: &Context,
+    theme: &Theme,
+    title: Option<&str>,
+    url: Option<&str>,
+    width: f64,
+) {
+    let mut font_description = pango::FontDescription::from_string(&theme.content.font);
+    font_description.set_size(pango::units_from_double(theme.content.size * 0.75));
+
+    ctx.set_source_rgb(
+        theme.content.color.0 as f64 / 255.0,
+        theme.content.color.1 as f64 / 255.0,
+        theme.content.color.2 as f64 / 255.0,
+    );
+
+    let half = width / 2.0;
+    let parts = vec![(title, Alignment::Left, 0.0), (url, Alignment::Right, half)];
+
+    for (text, alignment, x) in parts {
+        let text = match text {
+            Some(text) => text,
+            None => continue,
+        };
+
+        let layout = pangocairo::create_layout(ctx).expect("cannot create pango layout");
+
+        layout.set_font_description(Some(&font_description));
+        layout.set_width(pango::units_from_double(half));
+        layout.set_ellipsize(EllipsizeMode::Middle);
+        layout.set_alignment(alignment);
+        layout.set_text(text);
+
+        ctx.move_to(x, 0.0);
+        pangocairo::show_layout(ctx, &layout);
+    }
+
+    ctx.rectangle(0.0, HEADER_HEIGHT / 2.0 + theme.content.size, width, 0.5);
+    ctx.fill();
+}
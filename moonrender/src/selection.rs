@@ -0,0 +1,101 @@
+// a position in the page, as (line index, byte index in the line's text)
+pub type Position = (usize, usize);
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub anchor: Position,
+    pub cursor: Position,
+}
+
+impl Selection {
+    pub fn new(anchor: Position, cursor: Position) -> Self {
+        Self { anchor, cursor }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.cursor
+    }
+
+    // (start, end), in page order
+    pub fn range(&self) -> (Position, Position) {
+        if self.anchor <= self.cursor {
+            (self.anchor, self.cursor)
+        } else {
+            (self.cursor, self.anchor)
+        }
+    }
+
+    // selected byte range of the line with the given index and text length
+    pub fn range_for(&self, line: usize, len: usize) -> Option<(usize, usize)> {
+        let (start, end) = self.range();
+
+        if line < start.0 || line > end.0 {
+            return None;
+        }
+
+        let from = if line == start.0 { start.1 } else { 0 };
+        let to = if line == end.0 { end.1 } else { len };
+
+        Some((from.min(len), to.min(len)))
+    }
+}
+
+pub fn is_word_char(chr: char) -> bool {
+    chr.is_alphanumeric() || chr == '_'
+}
+
+// byte range of the word around the given index
+pub fn word_at(text: &str, index: usize) -> (usize, usize) {
+    let start = text[..index]
+        .char_indices()
+        .rev()
+        .take_while(|(_, chr)| is_word_char(*chr))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(index);
+
+    let end = text[index..]
+        .char_indices()
+        .find(|(_, chr)| !is_word_char(*chr))
+        .map(|(i, _)| index + i)
+        .unwrap_or_else(|| text.len());
+
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words() {
+        let cases = [
+            ("hello world", 2, (0, 5)),
+            ("hello world", 5, (0, 5)),
+            ("hello world", 6, (6, 11)),
+            ("hello world", 11, (6, 11)),
+            ("snake_case-word", 3, (0, 10)),
+            ("café au lait", 3, (0, 5)),
+            ("日本語 text", 3, (0, 9)),
+            ("a  b", 2, (2, 2)),
+            ("", 0, (0, 0)),
+        ];
+
+        for (text, index, expected) in cases.iter() {
+            assert_eq!(word_at(text, *index), *expected, "{:?} {}", text, index);
+        }
+    }
+
+    #[test]
+    fn line_ranges() {
+        let selection = Selection::new((3, 4), (1, 2));
+        assert_eq!(selection.range(), ((1, 2), (3, 4)));
+
+        assert_eq!(selection.range_for(0, 10), None);
+        assert_eq!(selection.range_for(1, 10), Some((2, 10)));
+        assert_eq!(selection.range_for(2, 10), Some((0, 10)));
+        assert_eq!(selection.range_for(3, 10), Some((0, 4)));
+        assert_eq!(selection.range_for(3, 3), Some((0, 3)));
+        assert_eq!(selection.range_for(4, 10), None);
+    }
+}
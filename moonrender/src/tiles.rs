@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+// pages are drawn in horizontal strips of this height, so scrolling only needs
+// to draw the strips that weren't visible before
+pub const TILE_HEIGHT: f64 = 512.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TileKey {
+    pub index: usize,
+    pub width: i32,
+
+    // page generation, which also changes with the theme
+    pub generation: u64,
+}
+
+impl TileKey {
+    pub fn top(&self) -> f64 {
+        self.index as f64 * TILE_HEIGHT
+    }
+}
+
+// paints a tile onto the page at its place
+pub fn composite(ctx: &cairo::Context, tile: &cairo::ImageSurface, key: TileKey, width: f64) {
+    ctx.set_source_surface(tile, 0.0, key.top());
+    ctx.rectangle(0.0, key.top(), width, TILE_HEIGHT);
+    ctx.fill();
+}
+
+struct Tile {
+    surface: cairo::ImageSurface,
+    last_used: u64,
+}
+
+impl Tile {
+    fn size(&self) -> usize {
+        self.surface.get_stride() as usize * self.surface.get_height() as usize
+    }
+}
+
+// drawn tiles, the least recently used ones are dropped when they take more
+// memory than the budget
+pub struct TileCache {
+    tiles: HashMap<TileKey, Tile>,
+
+    budget: usize,
+    clock: u64,
+}
+
+impl TileCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            tiles: HashMap::new(),
+
+            budget,
+            clock: 0,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    // drops the tiles that reach below y, and every tile of other widths
+    pub fn retain_above(&mut self, width: i32, y: f64) {
+        self.tiles
+            .retain(|key, _| key.width == width && key.top() + TILE_HEIGHT <= y);
+    }
+
+    // drops the tiles of every width that overlap the rows from top to bottom
+    pub fn remove_between(&mut self, top: f64, bottom: f64) {
+        self.tiles
+            .retain(|key, _| key.top() + TILE_HEIGHT <= top || key.top() >= bottom);
+    }
+
+    pub fn get(&mut self, key: &TileKey) -> Option<&cairo::ImageSurface> {
+        self.clock += 1;
+
+        let tile = self.tiles.get_mut(key)?;
+        tile.last_used = self.clock;
+
+        Some(&tile.surface)
+    }
+
+    pub fn insert(&mut self, key: TileKey, surface: cairo::ImageSurface) {
+        self.clock += 1;
+
+        self.tiles.insert(
+            key,
+            Tile {
+                surface,
+                last_used: self.clock,
+            },
+        );
+
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        let mut used: usize = self.tiles.values().map(Tile::size).sum();
+
+        // the newest tile always stays, it's about to be drawn
+        while used > self.budget && self.tiles.len() > 1 {
+            let oldest = self
+                .tiles
+                .iter()
+                .min_by_key(|(_, tile)| tile.last_used)
+                .map(|(key, _)| *key);
+
+            match oldest.and_then(|key| self.tiles.remove(&key)) {
+                Some(tile) => used -= tile.size(),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(index: usize, width: i32) -> TileKey {
+        TileKey {
+            index,
+            width,
+            generation: 1,
+        }
+    }
+
+    fn filled() -> TileCache {
+        let mut cache = TileCache::new(usize::MAX);
+
+        for index in 0..4 {
+            for width in &[100, 200] {
+                let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).unwrap();
+                cache.insert(key(index, *width), surface);
+            }
+        }
+
+        cache
+    }
+
+    fn kept(cache: &mut TileCache, width: i32) -> Vec<usize> {
+        (0..4)
+            .filter(|index| cache.get(&key(*index, width)).is_some())
+            .collect()
+    }
+
+    #[test]
+    fn remove_between() {
+        let mut cache = filled();
+        cache.remove_between(600.0, 700.0);
+        assert_eq!(kept(&mut cache, 100), [0, 2, 3]);
+        assert_eq!(kept(&mut cache, 200), [0, 2, 3]);
+
+        // a line across the edge of two tiles
+        let mut cache = filled();
+        cache.remove_between(500.0, 1100.0);
+        assert_eq!(kept(&mut cache, 100), [3]);
+
+        // touching a tile isn't overlapping it
+        let mut cache = filled();
+        cache.remove_between(512.0, 1024.0);
+        assert_eq!(kept(&mut cache, 100), [0, 2, 3]);
+    }
+
+    #[test]
+    fn retain_above() {
+        let mut cache = filled();
+        cache.retain_above(100, 1100.0);
+        assert_eq!(kept(&mut cache, 100), [0, 1]);
+        assert_eq!(kept(&mut cache, 200), []);
+    }
+}
@@ -1,32 +1,55 @@
-use crate::lines::Line;
-use crate::Theme;
+use super::{follow, index_at, link_at, pos_of, span_attributes, with_marks, CachedLayout, Marks};
+use crate::accessible::Role;
+use crate::lines::{LayoutKey, Line, Span};
+use crate::{Data, Msg as RendererMsg, Theme};
 use cairo::Context;
-use pango::{Alignment, Layout, Weight, WrapMode};
+use pango::{Alignment, WrapMode};
 
 pub struct Heading {
     line: String,
-    size: u8,
+    level: u8,
 
     x: f64,
     y: f64,
 
     width: f64,
     height: f64,
+
+    // distance between the top of the line and its text
+    spacing_above: f64,
+
+    layout: CachedLayout,
+    marks: Marks,
+
+    // styled ranges of the text, like bold words or links
+    spans: Vec<Span>,
 }
 
 impl Heading {
-    pub fn new(line: String, size: u8) -> Self {
+    pub fn new(line: String, level: u8) -> Self {
         Self {
             line,
-            size,
+            level,
 
             x: 0.0,
             y: 0.0,
 
             width: 0.0,
             height: 0.0,
+
+            spacing_above: 0.0,
+
+            layout: CachedLayout::default(),
+            marks: Marks::default(),
+
+            spans: vec![],
         }
     }
+
+    pub fn with_spans(mut self, spans: Vec<Span>) -> Self {
+        self.spans = spans;
+        self
+    }
 }
 
 impl Line for Heading {
@@ -38,39 +61,107 @@ impl Line for Heading {
         (self.width, self.height)
     }
 
-    fn draw(&mut self, ctx: &Context, pango: &Layout, theme: &Theme) {
-        let (x, y) = ctx.get_current_point();
+    fn get_role(&self) -> Role {
+        Role::Heading(self.level)
+    }
+
+    fn get_text(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+
+    fn get_index(&self, pos: (f64, f64)) -> Option<usize> {
+        let layout = self.layout.get()?;
+        Some(index_at(
+            layout,
+            &self.line,
+            pos.0 - self.x,
+            pos.1 - self.y - self.spacing_above,
+        ))
+    }
+
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        self.marks.set_selection(selection);
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        self.marks.set_matches(matches, current);
+    }
+
+    fn get_index_pos(&self, index: usize) -> Option<(f64, f64)> {
+        let (x, y) = pos_of(self.layout.get()?, index);
+        Some((self.x + x, self.y + self.spacing_above + y))
+    }
+
+    fn get_link_at(&self, pos: (f64, f64)) -> Option<&str> {
+        let layout = self.layout.get()?;
+        link_at(
+            layout,
+            &self.spans,
+            pos.0 - self.x,
+            pos.1 - self.y - self.spacing_above,
+        )
+    }
+
+    fn click_at(&mut self, data: &Data, pos: (f64, f64)) -> Option<RendererMsg> {
+        let url = self.get_link_at(pos)?.to_owned();
+        follow(data, &url)
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
         self.x = x;
         self.y = y;
+    }
+
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        let (layout, stale) = self.layout.update(pango, key);
+        if !stale {
+            return;
+        }
 
-        let w = ctx.clip_extents().2.min(theme.max_content_width);
-        pango.set_width(pango::units_from_double(w - (theme.margin * 2.0)));
+        let style = theme.heading.level(self.level);
+        self.spacing_above = style.spacing_above;
 
-        let font_description = &mut pango::FontDescription::from_string(&theme.heading.font);
-        font_description.set_weight(Weight::Ultraheavy);
-        font_description.set_size(pango::units_from_double(if self.size == 1 {
-            theme.heading.level1_size
-        } else {
-            theme.heading.level2_size
-        }));
+        let w = key.width.min(theme.max_content_width);
+        layout.set_width(pango::units_from_double(w - (theme.margin * 2.0)));
 
-        pango.set_spacing(pango::units_from_double(theme.heading.line_spacing));
-        pango.set_font_description(Some(font_description));
-        pango.set_alignment(Alignment::Left);
-        pango.set_wrap(WrapMode::Word);
-        pango.set_text(&self.line);
+        let font_description = &mut pango::FontDescription::from_string(&style.font);
+        font_description.set_weight(style.weight.into());
+        font_description.set_size(pango::units_from_double(style.size));
 
-        let (w, h) = pango.get_pixel_size();
+        layout.set_spacing(pango::units_from_double(style.line_spacing));
+        layout.set_font_description(Some(font_description));
+        layout.set_alignment(Alignment::Left);
+        layout.set_wrap(WrapMode::Word);
+        layout.set_text(&self.line);
+        layout.set_attributes(span_attributes(&self.spans, theme).as_ref());
+
+        let (w, h) = layout.get_pixel_size();
         self.width = w as f64;
-        self.height = h as f64 + theme.heading.line_spacing;
+        self.height = style.spacing_above + h as f64 + style.line_spacing + style.spacing_below;
+
+        self.marks.invalidate();
+    }
+
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let pango = match self.layout.get() {
+            Some(layout) => layout.clone(),
+            None => return, // not measured yet
+        };
 
+        if self.marks.take_changed() {
+            pango.set_attributes(
+                with_marks(span_attributes(&self.spans, theme), &self.marks, theme).as_ref(),
+            );
+        }
+
+        let style = theme.heading.level(self.level);
+
+        ctx.move_to(self.x, self.y + self.spacing_above);
         ctx.set_source_rgb(
-            theme.heading.color.0 as f64 / 255.0,
-            theme.heading.color.1 as f64 / 255.0,
-            theme.heading.color.2 as f64 / 255.0,
+            style.color.0 as f64 / 255.0,
+            style.color.1 as f64 / 255.0,
+            style.color.2 as f64 / 255.0,
         );
-        pangocairo::show_layout(ctx, pango);
-
-        ctx.rel_move_to(0.0, theme.heading.line_spacing / 2.0);
+        pangocairo::show_layout(ctx, &pango);
     }
 }
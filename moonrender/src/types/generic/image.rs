@@ -0,0 +1,390 @@
+use crate::accessible::Role;
+use crate::lines::{LayoutKey, Line};
+use crate::{format_size, Data, Theme};
+use anyhow::{anyhow, bail, Context as _, Result};
+use cairo::{Context, Format, ImageSurface};
+use image::{AnimationDecoder, ImageDecoder, ImageFormat, RgbaImage};
+use std::{io::Cursor, time::Duration};
+
+// gifs with frames faster than this are slowed down, like browsers do
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+
+// small files can claim huge sizes, so the size is checked before decoding
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    // of one frame
+    pub pixels: u64,
+    // of all decoded frames together
+    pub decoded_bytes: u64,
+}
+
+// pages that are a single image
+pub const PAGE_LIMITS: Limits = Limits {
+    pixels: 64 * 1024 * 1024,
+    decoded_bytes: 512 * 1024 * 1024,
+};
+
+// images below links, a page can have many of them
+pub const INLINE_LIMITS: Limits = Limits {
+    pixels: 4096 * 4096,
+    decoded_bytes: 64 * 1024 * 1024,
+};
+
+struct Frame {
+    surface: ImageSurface,
+    delay: Duration,
+}
+
+// pixels in cairo's format, which unlike its surfaces can be sent between
+// threads. decoding is slow, so inline images are decoded where they're
+// loaded and Image::new makes them into a line
+pub struct DecodedImage {
+    frames: Vec<DecodedFrame>,
+
+    dimensions: (u32, u32),
+    bytes: usize,
+}
+
+struct DecodedFrame {
+    pixels: Vec<u8>,
+    delay: Duration,
+}
+
+pub struct Image {
+    frames: Vec<Frame>,
+    frame: usize,
+
+    // size in pixels, and how many bytes the file was
+    dimensions: (u32, u32),
+    bytes: usize,
+
+    x: f64,
+    y: f64,
+
+    width: f64,
+    height: f64,
+
+    scale: f64,
+    // horizontal offset of images zoomed wider than the page
+    scroll: f64,
+}
+
+impl DecodedImage {
+    // decodes a png, jpeg or gif file, all frames of it for animated gifs
+    pub fn decode(bytes: &[u8], limits: Limits) -> Result<Self> {
+        let format = image::guess_format(bytes).context("Unknown image format")?;
+
+        let (frames, dimensions) = if format == ImageFormat::Gif {
+            let decoder =
+                image::gif::GifDecoder::new(Cursor::new(bytes)).context("Cannot decode image")?;
+
+            // every frame is decoded to the full size of the gif
+            let (width, height) = decoder.dimensions();
+            check_size(width, height, 0, limits)?;
+
+            let mut decoded = 0;
+            let mut frames = vec![];
+            for frame in decoder.into_frames() {
+                decoded = check_size(width, height, decoded, limits)?;
+
+                let frame = frame.context("Cannot decode image")?;
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                let delay = Duration::from_millis((numer / denom.max(1)) as u64);
+
+                frames.push(DecodedFrame {
+                    pixels: to_pixels(frame.buffer()),
+                    delay: delay.max(MIN_FRAME_DELAY),
+                });
+            }
+
+            (frames, (width, height))
+        } else {
+            let (width, height) = match format {
+                ImageFormat::Png => image::png::PngDecoder::new(Cursor::new(bytes))
+                    .context("Cannot decode image")?
+                    .dimensions(),
+                ImageFormat::Jpeg => image::jpeg::JpegDecoder::new(Cursor::new(bytes))
+                    .context("Cannot decode image")?
+                    .dimensions(),
+                _ => bail!("Unsupported image format {:?}", format),
+            };
+            check_size(width, height, 0, limits)?;
+
+            let image = image::load_from_memory_with_format(bytes, format)
+                .context("Cannot decode image")?
+                .to_rgba();
+
+            let frame = DecodedFrame {
+                pixels: to_pixels(&image),
+                delay: Duration::default(),
+            };
+
+            (vec![frame], image.dimensions())
+        };
+
+        if frames.is_empty() {
+            bail!("Image has no frames");
+        }
+
+        Ok(Self {
+            frames,
+
+            dimensions,
+            bytes: bytes.len(),
+        })
+    }
+}
+
+impl Image {
+    // a page that's a single image, decoded right away
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        Self::new(DecodedImage::decode(bytes, PAGE_LIMITS)?)
+    }
+
+    pub fn new(decoded: DecodedImage) -> Result<Self> {
+        let (width, height) = decoded.dimensions;
+
+        let frames = decoded
+            .frames
+            .into_iter()
+            .map(|frame| {
+                let surface = ImageSurface::create_for_data(
+                    frame.pixels,
+                    Format::ARgb32,
+                    width as i32,
+                    height as i32,
+                    width as i32 * 4,
+                )
+                .map_err(|e| anyhow!("Cannot create image surface: {:?}", e))?;
+
+                Ok(Frame {
+                    surface,
+                    delay: frame.delay,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            frames,
+            frame: 0,
+
+            dimensions: decoded.dimensions,
+            bytes: decoded.bytes,
+
+            x: 0.0,
+            y: 0.0,
+
+            width: 0.0,
+            height: 0.0,
+
+            scale: 1.0,
+            scroll: 0.0,
+        })
+    }
+
+    fn drawn_width(&self) -> f64 {
+        self.dimensions.0 as f64 * self.scale
+    }
+
+    fn max_scroll(&self) -> f64 {
+        ((self.drawn_width() - self.width) / 2.0).max(0.0)
+    }
+}
+
+impl Line for Image {
+    fn get_pos(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    fn get_size(&self) -> (f64, f64) {
+        (self.width, self.height)
+    }
+
+    fn get_role(&self) -> Role {
+        Role::Image
+    }
+
+    fn get_tooltip(&self, _data: &Data) -> Option<String> {
+        Some(format!(
+            "{}×{}, {}",
+            self.dimensions.0,
+            self.dimensions.1,
+            format_size(self.bytes)
+        ))
+    }
+
+    fn scroll(&mut self, dx: f64) -> bool {
+        let max = self.max_scroll();
+        let scroll = (self.scroll + dx).max(-max).min(max);
+
+        if (scroll - self.scroll).abs() < f64::EPSILON {
+            return false;
+        }
+
+        self.scroll = scroll;
+        true
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
+        self.x = x;
+        self.y = y;
+    }
+
+    // fits the image in the content width, then applies the zoom
+    fn measure(&mut self, _pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        let w = key.width.min(theme.max_content_width) - (theme.margin * 2.0);
+        let fit = (w / self.dimensions.0 as f64).min(1.0);
+
+        self.scale = fit * key.zoom;
+        self.width = w;
+        self.height = self.dimensions.1 as f64 * self.scale;
+
+        self.scroll = self.scroll.max(-self.max_scroll()).min(self.max_scroll());
+    }
+
+    fn draw(&mut self, ctx: &Context, _theme: &Theme) {
+        let frame = match self.frames.get(self.frame) {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        // centered, images wider than the page can be scrolled sideways
+        let x = self.x + (self.width - self.drawn_width()) / 2.0 - self.scroll;
+
+        ctx.save();
+
+        ctx.translate(x, self.y);
+        ctx.scale(self.scale, self.scale);
+        ctx.set_source_surface(&frame.surface, 0.0, 0.0);
+        ctx.paint();
+
+        ctx.restore();
+    }
+
+    fn animate(&mut self, elapsed: Duration) -> Option<Duration> {
+        if self.frames.len() < 2 {
+            return None;
+        }
+
+        let total: Duration = self.frames.iter().map(|frame| frame.delay).sum();
+        let total = total.as_millis().max(1);
+
+        // where we are in the current loop of the animation
+        let mut left = elapsed.as_millis() % total;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let delay = frame.delay.as_millis();
+            if left < delay {
+                self.frame = i;
+                return Some(Duration::from_millis((delay - left) as u64));
+            }
+
+            left -= delay;
+        }
+
+        None
+    }
+}
+
+// cairo wants premultiplied alpha, in native endian 32 bit pixels. rows are
+// 4 bytes a pixel with no padding, the stride cairo uses for this format
+fn to_pixels(image: &RgbaImage) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(image.len());
+
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let premultiply = |c: u8| c as u32 * a as u32 / 255;
+
+        let argb =
+            ((a as u32) << 24) | (premultiply(r) << 16) | (premultiply(g) << 8) | premultiply(b);
+
+        pixels.extend_from_slice(&argb.to_ne_bytes());
+    }
+
+    pixels
+}
+
+// checks that one more frame of this size fits in the limits, given how many
+// bytes the frames decoded so far take. returns the bytes with this frame
+pub fn check_size(width: u32, height: u32, decoded: u64, limits: Limits) -> Result<u64> {
+    let pixels = width as u64 * height as u64;
+    if pixels > limits.pixels {
+        bail!("Image is too large, {}×{} pixels", width, height);
+    }
+
+    // decoded frames are kept as cairo surfaces, 4 bytes a pixel
+    let decoded = decoded + pixels * 4;
+    if decoded > limits.decoded_bytes {
+        bail!(
+            "Image is too large, {} decoded",
+            format_size(decoded as usize)
+        );
+    }
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_limits() {
+        let check = |width, height, decoded| check_size(width, height, decoded, PAGE_LIMITS);
+
+        assert_eq!(check(10, 10, 0).unwrap(), 400);
+        assert_eq!(check(10, 10, 400).unwrap(), 800);
+        assert_eq!(check(0, 0, 0).unwrap(), 0);
+
+        // claimed sizes that don't fit, before anything is decoded
+        assert!(check(u32::MAX, u32::MAX, 0).is_err());
+        assert!(check(16384, 16384, 0).is_err());
+        assert!(check(8192, 8192, 0).is_ok());
+
+        // frames that fit alone but not together
+        let frame = 8192 * 8192 * 4;
+        let max = PAGE_LIMITS.decoded_bytes;
+        assert!(check(8192, 8192, max - frame).is_ok());
+        assert!(check(8192, 8192, max - frame + 1).is_err());
+    }
+
+    #[test]
+    fn inline_limits() {
+        let check = |width, height, decoded| check_size(width, height, decoded, INLINE_LIMITS);
+
+        // what a page can show, a preview can't
+        assert!(check_size(8192, 8192, 0, PAGE_LIMITS).is_ok());
+        assert!(check(8192, 8192, 0).is_err());
+        assert!(check(4096, 4096, 0).is_ok());
+        assert!(check(4097, 4096, 0).is_err());
+
+        // only one frame of a gif that large
+        let frame = 4096 * 4096 * 4;
+        assert_eq!(check(4096, 4096, 0).unwrap(), frame);
+        assert!(check(4096, 4096, frame).is_err());
+        assert!(check(1024, 1024, frame - 1024 * 1024 * 4).is_ok());
+    }
+
+    #[test]
+    fn decode() {
+        // a 2×1 png, one red and one half transparent white pixel
+        let mut png = vec![];
+        let pixels = [255, 0, 0, 255, 255, 255, 255, 128];
+        image::png::PngEncoder::new(&mut png)
+            .encode(&pixels, 2, 1, image::ColorType::Rgba8)
+            .unwrap();
+
+        let decoded = DecodedImage::decode(&png, INLINE_LIMITS).unwrap();
+        assert_eq!(decoded.dimensions, (2, 1));
+        assert_eq!(decoded.bytes, png.len());
+        assert_eq!(decoded.frames.len(), 1);
+
+        let argb: Vec<u32> = decoded.frames[0]
+            .pixels
+            .chunks(4)
+            .map(|pixel| u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]))
+            .collect();
+        assert_eq!(argb, [0xffff_0000, 0x8080_8080]);
+
+        assert!(DecodedImage::decode(b"not an image", INLINE_LIMITS).is_err());
+    }
+}
@@ -1,7 +1,25 @@
-use crate::lines::Line;
-use crate::{Data, Msg as RendererMsg, Theme};
+use super::{follow, index_at, pos_of, with_marks, CachedLayout, DecodedImage, Image, Marks};
+use crate::accessible::Role;
+use crate::lines::{LayoutKey, Line};
+use crate::{Data, InlineImages, Msg as RendererMsg, Theme};
+use anyhow::Result;
 use cairo::Context;
-use pango::{Alignment, Layout, WrapMode};
+use pango::{Alignment, WrapMode};
+use std::time::Duration;
+
+// height of the box shown until an inline image is loaded
+const PLACEHOLDER_HEIGHT: f64 = 48.0;
+
+// the image below links to images, see Theme::inline_images
+enum Preview {
+    // until it's scrolled into view, or its placeholder is clicked
+    Waiting,
+    // clicked, requested the next time the page asks
+    Clicked,
+    Loading,
+    Loaded(Box<Image>),
+    Failed,
+}
 
 pub struct Link {
     url: String,
@@ -12,10 +30,29 @@ pub struct Link {
 
     width: f64,
     height: f64,
+
+    layout: CachedLayout,
+    marks: Marks,
+
+    focused: bool,
+
+    // only for links to images
+    preview: Option<Preview>,
+    inline_images: InlineImages,
+
+    // size of the link text, the preview goes below it
+    text_width: f64,
+    text_height: f64,
 }
 
 impl Link {
     pub fn new(line: String, url: String) -> Self {
+        let preview = if is_image(&url) {
+            Some(Preview::Waiting)
+        } else {
+            None
+        };
+
         Self {
             url,
             line,
@@ -25,8 +62,65 @@ impl Link {
 
             width: 0.0,
             height: 0.0,
+
+            layout: CachedLayout::default(),
+            marks: Marks::default(),
+
+            focused: false,
+
+            preview,
+            inline_images: InlineImages::Off,
+
+            text_width: 0.0,
+            text_height: 0.0,
         }
     }
+
+    // the preview, if it's shown with the current theme
+    fn preview(&self) -> Option<&Preview> {
+        if self.inline_images == InlineImages::Off {
+            return None;
+        }
+
+        self.preview.as_ref()
+    }
+
+    fn preview_y(&self, theme: &Theme) -> f64 {
+        self.y + self.text_height + theme.link.line_spacing
+    }
+
+    fn draw_placeholder(&self, ctx: &Context, theme: &Theme, text: &str) {
+        let y = self.preview_y(theme);
+
+        ctx.set_source_rgb(
+            theme.link.color.0 as f64 / 255.0,
+            theme.link.color.1 as f64 / 255.0,
+            theme.link.color.2 as f64 / 255.0,
+        );
+
+        ctx.set_line_width(1.0);
+        ctx.rectangle(
+            self.x + 0.5,
+            y + 0.5,
+            self.width - 1.0,
+            PLACEHOLDER_HEIGHT - 1.0,
+        );
+        ctx.stroke();
+
+        let mut font_description = pango::FontDescription::from_string(&theme.link.font);
+        font_description.set_size(pango::units_from_double(theme.link.size));
+
+        let layout = pangocairo::create_layout(ctx).expect("Cannot create pango layout");
+        layout.set_font_description(Some(&font_description));
+        layout.set_text(text);
+
+        let (w, h) = layout.get_pixel_size();
+        ctx.move_to(
+            self.x + (self.width - w as f64) / 2.0,
+            y + (PLACEHOLDER_HEIGHT - h as f64) / 2.0,
+        );
+        pangocairo::show_layout(ctx, &layout);
+    }
 }
 
 impl Line for Link {
@@ -38,59 +132,242 @@ impl Line for Link {
         (self.width, self.height)
     }
 
+    fn get_role(&self) -> Role {
+        Role::Link
+    }
+
+    fn get_text(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+
+    fn get_index(&self, pos: (f64, f64)) -> Option<usize> {
+        let layout = self.layout.get()?;
+        Some(index_at(layout, &self.line, pos.0 - self.x, pos.1 - self.y))
+    }
+
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        self.marks.set_selection(selection);
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        self.marks.set_matches(matches, current);
+    }
+
+    fn get_index_pos(&self, index: usize) -> Option<(f64, f64)> {
+        let (x, y) = pos_of(self.layout.get()?, index);
+        Some((self.x + x, self.y + y))
+    }
+
+    fn is_focusable(&self) -> bool {
+        true
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    fn get_link_at(&self, _pos: (f64, f64)) -> Option<&str> {
+        Some(&self.url)
+    }
+
     fn get_tooltip(&self, data: &Data) -> Option<String> {
-        if let Some(url) = &data.url {
-            if let Ok(new) = url.join(&self.url) {
-                return Some(new.to_string());
-            }
+        match data.resolve(&self.url) {
+            Ok(url) => Some(url.to_string()),
+            Err(_) => Some(self.url.clone()),
         }
-
-        Some(self.url.clone())
     }
 
-    fn draw(&mut self, ctx: &Context, pango: &Layout, theme: &Theme) {
-        let (x, y) = ctx.get_current_point();
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
         self.x = x;
         self.y = y;
+    }
 
-        let w = ctx.clip_extents().2.min(theme.max_content_width);
-        pango.set_width(pango::units_from_double(w - (theme.margin * 2.0)));
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        self.inline_images = theme.inline_images;
+        let w = key.width.min(theme.max_content_width) - (theme.margin * 2.0);
 
-        let mut font_description = pango::FontDescription::from_string(&theme.link.font);
-        font_description.set_size(pango::units_from_double(theme.link.size));
+        let (layout, stale) = self.layout.update(pango, key);
+        if stale {
+            layout.set_width(pango::units_from_double(w));
+
+            let mut font_description = pango::FontDescription::from_string(&theme.link.font);
+            font_description.set_size(pango::units_from_double(theme.link.size));
+
+            layout.set_spacing(pango::units_from_double(theme.link.line_spacing));
+            layout.set_alignment(Alignment::Left);
+            layout.set_wrap(WrapMode::Word);
+            layout.set_font_description(Some(&font_description));
+            layout.set_text(&self.line);
+
+            let (text_width, text_height) = layout.get_pixel_size();
+            self.text_width = text_width as f64;
+            self.text_height = text_height as f64;
+
+            self.marks.invalidate();
+        }
+
+        self.width = self.text_width;
+        self.height = self.text_height + theme.link.line_spacing;
+
+        let preview_height = match &mut self.preview {
+            _ if self.inline_images == InlineImages::Off => None,
+            None => None,
+            Some(Preview::Loaded(image)) => {
+                image.measure(pango, theme, key);
+                Some(image.get_size().1)
+            }
+            Some(_) => Some(PLACEHOLDER_HEIGHT),
+        };
+
+        // as wide as the page, so clicks on the preview hit the link
+        if let Some(preview_height) = preview_height {
+            self.width = w;
+            self.height += preview_height + theme.paragraph_spacing;
+        }
+    }
 
-        pango.set_spacing(pango::units_from_double(theme.link.line_spacing));
-        pango.set_alignment(Alignment::Left);
-        pango.set_wrap(WrapMode::Word);
-        pango.set_font_description(Some(&font_description));
-        pango.set_text(&self.line);
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let pango = match self.layout.get() {
+            Some(layout) => layout.clone(),
+            None => return, // not measured yet
+        };
 
-        let (w, h) = pango.get_pixel_size();
-        self.width = w as f64;
-        self.height = h as f64 + theme.link.line_spacing;
+        if self.marks.take_changed() {
+            pango.set_attributes(with_marks(None, &self.marks, theme).as_ref());
+        }
+
+        let (x, y) = (self.x, self.y);
 
+        ctx.move_to(x, y);
         ctx.set_source_rgb(
             theme.link.color.0 as f64 / 255.0,
             theme.link.color.1 as f64 / 255.0,
             theme.link.color.2 as f64 / 255.0,
         );
-        pangocairo::show_layout(ctx, pango);
+        pangocairo::show_layout(ctx, &pango);
+
+        let placeholder = match self.preview() {
+            None | Some(Preview::Loaded(_)) => None,
+            Some(Preview::Waiting) if self.inline_images == InlineImages::Click => {
+                Some("Click to load image")
+            }
+            Some(Preview::Failed) => Some("Cannot load image"),
+            Some(_) => Some("Loading image…"),
+        };
+
+        if let Some(text) = placeholder {
+            self.draw_placeholder(ctx, theme, text);
+        } else if self.inline_images != InlineImages::Off {
+            let preview_y = self.preview_y(theme);
+
+            if let Some(Preview::Loaded(image)) = &mut self.preview {
+                image.set_pos((x, preview_y));
+                image.draw(ctx, theme);
+            }
+        }
+
+        if self.focused {
+            let padding = theme.focus.padding;
 
-        ctx.rel_move_to(0.0, theme.link.line_spacing / 2.0);
+            ctx.set_source_rgb(
+                theme.focus.color.0 as f64 / 255.0,
+                theme.focus.color.1 as f64 / 255.0,
+                theme.focus.color.2 as f64 / 255.0,
+            );
+
+            ctx.set_line_width(theme.focus.width);
+            ctx.rectangle(
+                x - padding,
+                y - padding,
+                self.text_width + padding * 2.0,
+                self.text_height + padding * 2.0,
+            );
+            ctx.stroke();
+        }
     }
 
-    fn click(&mut self, data: &Data) -> Option<RendererMsg> {
-        if let Some(url) = &data.url {
-            match url.join(&self.url) {
-                Ok(new) => Some(RendererMsg::Goto(new.to_string())),
+    // clicks on the placeholder load the image, the rest follows the link
+    fn click_at(&mut self, data: &Data, pos: (f64, f64)) -> Option<RendererMsg> {
+        let below_text = pos.1 > self.y + self.text_height;
+
+        if below_text && self.inline_images == InlineImages::Click {
+            if let Some(Preview::Waiting) | Some(Preview::Failed) = self.preview {
+                self.preview = Some(Preview::Clicked);
+                return Some(RendererMsg::Redraw);
+            }
+        }
+
+        self.click(data)
+    }
+
+    fn take_request(&mut self) -> Option<String> {
+        let wanted = match self.preview {
+            Some(Preview::Waiting) => self.inline_images == InlineImages::Auto,
+            Some(Preview::Clicked) => true,
+            _ => false,
+        };
+
+        if !wanted {
+            return None;
+        }
+
+        self.preview = Some(Preview::Loading);
+        Some(self.url.clone())
+    }
+
+    fn set_content(&mut self, content: Result<DecodedImage>) {
+        if let Some(Preview::Loading) = self.preview {
+            self.preview = match content.and_then(Image::new) {
+                Ok(image) => Some(Preview::Loaded(Box::new(image))),
                 Err(e) => {
-                    log::error!("Not following link since: {}", e);
-                    None
+                    log::warn!("Cannot load inline image {}: {:#}", self.url, e);
+                    Some(Preview::Failed)
                 }
-            }
-        } else {
-            log::warn!("Not following link since renderer doesn't have a URL");
-            None
+            };
+        }
+    }
+
+    fn animate(&mut self, elapsed: Duration) -> Option<Duration> {
+        match &mut self.preview {
+            Some(Preview::Loaded(image)) => image.animate(elapsed),
+            _ => None,
+        }
+    }
+
+    fn click(&mut self, data: &Data) -> Option<RendererMsg> {
+        follow(data, &self.url)
+    }
+}
+
+// links are only previewed when they look like images, the server isn't asked
+fn is_image(url: &str) -> bool {
+    let path = url.split(|c| c == '?' || c == '#').next().unwrap_or("");
+    let path = path.to_ascii_lowercase();
+
+    [".png", ".jpg", ".jpeg", ".gif"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_links() {
+        let cases = [
+            ("photo.png", true),
+            ("/a/b/PHOTO.JPG", true),
+            ("gemini://host/anim.gif?size=large", true),
+            ("photo.jpeg#top", true),
+            ("photo.png.html", false),
+            ("page?photo.png", false),
+            ("gemini://host/", false),
+            ("", false),
+        ];
+
+        for (url, expected) in cases.iter() {
+            assert_eq!(is_image(url), *expected, "{}", url);
         }
     }
 }
@@ -1,30 +1,64 @@
-use crate::lines::Line;
-use crate::Theme;
+use super::{follow, index_at, link_at, pos_of, span_attributes, with_marks, CachedLayout, Marks};
+use crate::accessible::Role;
+use crate::lines::{LayoutKey, Line, Span};
+use crate::{Data, Msg as RendererMsg, Theme};
 use cairo::Context;
-use pango::{Alignment, Layout, WrapMode};
+use pango::{Alignment, WrapMode};
 
 pub struct List {
     line: String,
+    // the theme's bullet if there's none, like for unordered lists
+    bullet: Option<String>,
+    // how far the item is nested in other lists, 0 for top level items
+    depth: usize,
 
     x: f64,
     y: f64,
 
     width: f64,
     height: f64,
+
+    layout: CachedLayout,
+    bullet_layout: CachedLayout,
+    bullet_width: f64,
+    indent: f64,
+    marks: Marks,
+
+    // styled ranges of the text, like bold words or links
+    spans: Vec<Span>,
 }
 
 impl List {
     pub fn new(line: String) -> Self {
+        Self::nested(line, None, 0)
+    }
+
+    pub fn nested(line: String, bullet: Option<String>, depth: usize) -> Self {
         Self {
             line,
+            bullet,
+            depth,
 
             x: 0.0,
             y: 0.0,
 
             width: 0.0,
             height: 0.0,
+
+            layout: CachedLayout::default(),
+            bullet_layout: CachedLayout::default(),
+            bullet_width: 0.0,
+            indent: 0.0,
+            marks: Marks::default(),
+
+            spans: vec![],
         }
     }
+
+    pub fn with_spans(mut self, spans: Vec<Span>) -> Self {
+        self.spans = spans;
+        self
+    }
 }
 
 impl Line for List {
@@ -36,48 +70,128 @@ impl Line for List {
         (self.width, self.height)
     }
 
-    fn draw(&mut self, ctx: &Context, pango: &Layout, theme: &Theme) {
-        let (x, y) = ctx.get_current_point();
+    fn get_role(&self) -> Role {
+        Role::ListItem
+    }
+
+    fn get_text(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+
+    fn get_index(&self, pos: (f64, f64)) -> Option<usize> {
+        let layout = self.layout.get()?;
+        Some(index_at(
+            layout,
+            &self.line,
+            pos.0 - self.x - self.indent,
+            pos.1 - self.y,
+        ))
+    }
+
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        self.marks.set_selection(selection);
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        self.marks.set_matches(matches, current);
+    }
+
+    fn get_index_pos(&self, index: usize) -> Option<(f64, f64)> {
+        let (x, y) = pos_of(self.layout.get()?, index);
+        Some((self.x + self.indent + x, self.y + y))
+    }
+
+    fn get_link_at(&self, pos: (f64, f64)) -> Option<&str> {
+        let layout = self.layout.get()?;
+        link_at(
+            layout,
+            &self.spans,
+            pos.0 - self.x - self.indent,
+            pos.1 - self.y,
+        )
+    }
+
+    fn click_at(&mut self, data: &Data, pos: (f64, f64)) -> Option<RendererMsg> {
+        let url = self.get_link_at(pos)?.to_owned();
+        follow(data, &url)
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
         self.x = x;
         self.y = y;
+    }
 
-        let w = ctx.clip_extents().2.min(theme.max_content_width);
-        pango.set_width(pango::units_from_double(w - (theme.margin * 2.0)));
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        let (layout, stale) = self.layout.update(pango, key);
+        let (bullet, _) = self.bullet_layout.update(pango, key);
+
+        if !stale {
+            return;
+        }
+
+        // nested items move right by the width of a bullet
+        let w = key.width.min(theme.max_content_width);
+        self.bullet_width = w as f64 * theme.margin;
+        self.indent = self.depth as f64 * (self.bullet_width + theme.list.bullet_padding);
+
+        layout.set_width(pango::units_from_double(
+            w - (theme.margin * 2.0) - self.indent,
+        ));
 
         let mut font_description = pango::FontDescription::from_string(&theme.list.font);
         font_description.set_size(pango::units_from_double(theme.list.size));
 
-        pango.set_spacing(pango::units_from_double(theme.list.line_spacing));
-        pango.set_alignment(Alignment::Left);
-        pango.set_wrap(WrapMode::Word);
-        pango.set_font_description(Some(&font_description));
-        pango.set_text(&self.line);
+        layout.set_spacing(pango::units_from_double(theme.list.line_spacing));
+        layout.set_alignment(Alignment::Left);
+        layout.set_wrap(WrapMode::Word);
+        layout.set_font_description(Some(&font_description));
+        layout.set_text(&self.line);
+        layout.set_attributes(span_attributes(&self.spans, theme).as_ref());
 
-        let (w, h) = pango.get_pixel_size();
-        self.width = w as f64;
+        let (w, h) = layout.get_pixel_size();
+        self.width = w as f64 + self.indent;
         self.height = h as f64 + theme.link.line_spacing;
 
+        // bullet
+
+        bullet.set_width(pango::units_from_double(self.bullet_width));
+        bullet.set_spacing(pango::units_from_double(theme.list.line_spacing));
+        bullet.set_font_description(Some(&font_description));
+        bullet.set_alignment(Alignment::Right);
+        bullet.set_text(self.bullet.as_deref().unwrap_or(&theme.list.bullet));
+
+        self.marks.invalidate();
+    }
+
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let pango = match self.layout.get() {
+            Some(layout) => layout.clone(),
+            None => return, // not measured yet
+        };
+
+        if self.marks.take_changed() {
+            pango.set_attributes(
+                with_marks(span_attributes(&self.spans, theme), &self.marks, theme).as_ref(),
+            );
+        }
+
+        ctx.move_to(self.x + self.indent, self.y);
         ctx.set_source_rgb(
             theme.list.color.0 as f64 / 255.0,
             theme.list.color.1 as f64 / 255.0,
             theme.list.color.2 as f64 / 255.0,
         );
 
-        pangocairo::show_layout(ctx, pango);
+        pangocairo::show_layout(ctx, &pango);
 
         // Draw bullet
 
-        let w = ctx.clip_extents().2.min(theme.max_content_width);
-        let w = w as f64 * theme.margin;
-
-        pango.set_width(pango::units_from_double(w));
-        ctx.rel_move_to(-(w + theme.list.bullet_padding), 0.0);
-
-        pango.set_alignment(Alignment::Right);
-        pango.set_text(&theme.list.bullet);
-        pangocairo::show_layout(ctx, pango);
-
-        // reset
-        ctx.rel_move_to(w + theme.list.bullet_padding, theme.link.line_spacing / 2.0);
+        if let Some(bullet) = self.bullet_layout.get() {
+            ctx.move_to(
+                self.x + self.indent - (self.bullet_width + theme.list.bullet_padding),
+                self.y,
+            );
+            pangocairo::show_layout(ctx, bullet);
+        }
     }
 }
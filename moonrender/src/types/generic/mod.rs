@@ -1,11 +1,243 @@
 mod heading;
+mod image;
 mod link;
 mod list;
 mod preformat;
+mod quote;
+mod quoted;
+mod table;
 mod text;
 
+pub use self::image::{DecodedImage, Image, Limits, INLINE_LIMITS, PAGE_LIMITS};
 pub use heading::Heading;
 pub use link::Link;
 pub use list::List;
 pub use preformat::Preformat;
+pub use quote::Quote;
+pub use quoted::Quoted;
+pub use table::Table;
 pub use text::Text;
+
+use crate::config::Color;
+use crate::lines::{LayoutKey, Span};
+use crate::{Data, Msg as RendererMsg, Theme};
+use pango::{AttrList, Attribute, Layout};
+
+// a line's pango layout, along with the key it was configured for
+#[derive(Default)]
+struct CachedLayout {
+    layout: Option<Layout>,
+    key: Option<LayoutKey>,
+}
+
+impl CachedLayout {
+    // returns the layout, and whether it needs to be configured again
+    fn update(&mut self, pango: &pango::Context, key: LayoutKey) -> (Layout, bool) {
+        let layout = self
+            .layout
+            .get_or_insert_with(|| Layout::new(pango))
+            .clone();
+
+        let stale = self.key != Some(key);
+        self.key = Some(key);
+
+        (layout, stale)
+    }
+
+    fn get(&self) -> Option<&Layout> {
+        self.layout.as_ref()
+    }
+}
+
+// returns the byte index of `text` closest to the given position, relative to
+// the top left corner of the layout
+fn index_at(layout: &Layout, text: &str, x: f64, y: f64) -> usize {
+    let (_, index, trailing) =
+        layout.xy_to_index(pango::units_from_double(x), pango::units_from_double(y));
+
+    let index = index.max(0) as usize;
+
+    // trailing is the amount of characters after index the position is at
+    text.get(index..)
+        .and_then(|rest| rest.char_indices().nth(trailing.max(0) as usize))
+        .map(|(i, _)| index + i)
+        .unwrap_or_else(|| text.len())
+}
+
+// position of the byte index in the layout, relative to its top left corner
+fn pos_of(layout: &Layout, index: usize) -> (f64, f64) {
+    let rect = layout.index_to_pos(index as i32);
+    (
+        pango::units_to_double(rect.x),
+        pango::units_to_double(rect.y),
+    )
+}
+
+// target of the link span at the position, relative to the top left corner of
+// the layout. unlike index_at, only exactly on the text counts
+fn link_at<'a>(layout: &Layout, spans: &'a [Span], x: f64, y: f64) -> Option<&'a str> {
+    let (inside, index, _) =
+        layout.xy_to_index(pango::units_from_double(x), pango::units_from_double(y));
+
+    if !inside {
+        return None;
+    }
+
+    spans
+        .iter()
+        .filter(|span| span.contains(index.max(0) as usize))
+        .find_map(|span| span.link.as_deref())
+}
+
+// goes to the url, relative to the page's
+fn follow(data: &Data, url: &str) -> Option<RendererMsg> {
+    match data.resolve(url) {
+        Ok(new) => Some(RendererMsg::Goto(new.to_string())),
+        Err(e) => {
+            log::error!("Not following link since: {}", e);
+            None
+        }
+    }
+}
+
+// the spans as pango attributes. links get the theme's link color unless the
+// span has its own
+fn span_attributes(spans: &[Span], theme: &Theme) -> Option<AttrList> {
+    if spans.is_empty() {
+        return None;
+    }
+
+    let monospace = pango::FontDescription::from_string(&theme.monospace.font)
+        .get_family()
+        .map(|family| family.to_string())
+        .unwrap_or_else(|| "monospace".to_owned());
+
+    let attrs = AttrList::new();
+
+    for span in spans {
+        let mut styles = vec![];
+
+        if span.bold {
+            styles.push(Attribute::new_weight(pango::Weight::Bold));
+        }
+        if span.italic {
+            styles.push(Attribute::new_style(pango::Style::Italic));
+        }
+        if span.monospace {
+            styles.push(Attribute::new_family(&monospace));
+        }
+        if span.strikethrough {
+            styles.push(Attribute::new_strikethrough(true));
+        }
+        if span.link.is_some() {
+            styles.push(Attribute::new_underline(pango::Underline::Single));
+        }
+
+        let color = match (&span.color, &span.link) {
+            (Some(color), _) => Some(color),
+            (None, Some(_)) => Some(&theme.link.color),
+            (None, None) => None,
+        };
+
+        if let Some(color) = color {
+            styles.push(Attribute::new_foreground(
+                color.0 as u16 * 257,
+                color.1 as u16 * 257,
+                color.2 as u16 * 257,
+            ));
+        }
+
+        for mut attr in styles.into_iter().flatten() {
+            attr.set_start_index(span.start as u32);
+            attr.set_end_index(span.end as u32);
+            attrs.insert(attr);
+        }
+    }
+
+    Some(attrs)
+}
+
+// ranges of a line's text that are drawn highlighted
+#[derive(Default)]
+struct Marks {
+    selection: Option<(usize, usize)>,
+
+    matches: Vec<(usize, usize)>,
+    current_match: Option<usize>,
+
+    changed: bool,
+}
+
+impl Marks {
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        if self.selection != selection {
+            self.selection = selection;
+            self.changed = true;
+        }
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        if self.matches != matches || self.current_match != current {
+            self.matches = matches;
+            self.current_match = current;
+            self.changed = true;
+        }
+    }
+
+    // the layout needs its attributes set again, like after the theme changed
+    fn invalidate(&mut self) {
+        self.changed = true;
+    }
+
+    // whether the marks changed since the last call, so the layout needs new
+    // attributes
+    fn take_changed(&mut self) -> bool {
+        std::mem::replace(&mut self.changed, false)
+    }
+}
+
+fn insert_colors(attrs: &AttrList, (start, end): (usize, usize), fg: &Color, bg: &Color) {
+    let colors = vec![
+        Attribute::new_foreground(fg.0 as u16 * 257, fg.1 as u16 * 257, fg.2 as u16 * 257),
+        Attribute::new_background(bg.0 as u16 * 257, bg.1 as u16 * 257, bg.2 as u16 * 257),
+    ];
+
+    for mut attr in colors.into_iter().flatten() {
+        attr.set_start_index(start as u32);
+        attr.set_end_index(end as u32);
+        attrs.insert(attr);
+    }
+}
+
+// adds the search match and selection colors to `attrs`, if there are any
+fn with_marks(attrs: Option<AttrList>, marks: &Marks, theme: &Theme) -> Option<AttrList> {
+    let selection = marks.selection.filter(|(start, end)| start != end);
+
+    if marks.matches.is_empty() && selection.is_none() {
+        return attrs;
+    }
+
+    let attrs = attrs.unwrap_or_else(AttrList::new);
+
+    // later attributes win, so the selection goes last
+    for (i, range) in marks.matches.iter().enumerate() {
+        let bg = if marks.current_match == Some(i) {
+            &theme.find.current_background
+        } else {
+            &theme.find.background
+        };
+
+        insert_colors(&attrs, *range, &theme.find.foreground, bg);
+    }
+
+    if let Some(range) = selection {
+        insert_colors(
+            &attrs,
+            range,
+            &theme.selection.foreground,
+            &theme.selection.background,
+        );
+    }
+
+    Some(attrs)
+}
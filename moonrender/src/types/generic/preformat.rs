@@ -1,29 +1,104 @@
-use crate::lines::Line;
-use crate::Theme;
-use cairo::Context;
-use pango::{Alignment, Layout, WrapMode};
+use super::{index_at, pos_of, with_marks, CachedLayout, Marks};
+use crate::accessible::Role;
+use crate::highlight::{self, Span};
+use crate::lines::{LayoutKey, Line};
+use crate::{Data, Theme};
+use cairo::{Context, LinearGradient};
+use pango::{Alignment, AttrList, Attribute};
+
+// width of the fade drawn over clipped edges
+const INDICATOR_WIDTH: f64 = 24.0;
 
 pub struct Preformat {
     line: String,
+    alt: Option<String>,
+
+    // the first word of the alt text, like ```rust
+    language: Option<String>,
+    spans: Option<Vec<Span>>,
+    // the theme the spans were highlighted with
+    spans_theme: Option<u64>,
 
     x: f64,
     y: f64,
 
     width: f64,
     height: f64,
+
+    content_width: f64,
+    visible_width: f64,
+    scroll: f64,
+
+    layout: CachedLayout,
+    marks: Marks,
 }
 
 impl Preformat {
-    pub fn new(line: String) -> Self {
+    pub fn new(line: String, alt: Option<String>) -> Self {
+        let language = alt
+            .as_ref()
+            .and_then(|alt| alt.split_whitespace().next())
+            .map(|language| language.to_owned());
+
         Self {
             line,
+            alt,
+
+            language,
+            spans: None,
+            spans_theme: None,
 
             x: 0.0,
             y: 0.0,
 
             width: 0.0,
             height: 0.0,
+
+            content_width: 0.0,
+            visible_width: 0.0,
+            scroll: 0.0,
+
+            layout: CachedLayout::default(),
+            marks: Marks::default(),
+        }
+    }
+
+    fn max_scroll(&self) -> f64 {
+        (self.content_width - self.width).max(0.0)
+    }
+
+    fn get_attributes(&mut self, theme: &Theme) -> Option<AttrList> {
+        if !theme.highlight.enabled {
+            return None;
+        }
+
+        let line = &self.line;
+        let language = &self.language;
+
+        // highlighting is expensive, only do it once
+        let spans = self.spans.get_or_insert_with(|| {
+            language
+                .as_ref()
+                .and_then(|language| {
+                    highlight::highlight(line, language, &theme.highlight, &theme.monospace.color)
+                })
+                .unwrap_or_default()
+        });
+
+        let attrs = AttrList::new();
+        for span in spans.iter() {
+            if let Some(mut attr) = Attribute::new_foreground(
+                span.color.0 as u16 * 257,
+                span.color.1 as u16 * 257,
+                span.color.2 as u16 * 257,
+            ) {
+                attr.set_start_index(span.start as u32);
+                attr.set_end_index(span.end as u32);
+                attrs.insert(attr);
+            }
         }
+
+        Some(attrs)
     }
 }
 
@@ -36,38 +111,147 @@ impl Line for Preformat {
         (self.width, self.height)
     }
 
-    fn draw(&mut self, ctx: &Context, pango: &Layout, theme: &Theme) {
-        let (x, y) = ctx.get_current_point();
+    fn get_role(&self) -> Role {
+        Role::Preformatted
+    }
+
+    fn get_tooltip(&self, _data: &Data) -> Option<String> {
+        self.alt.clone()
+    }
+
+    fn get_text(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+
+    fn get_index(&self, pos: (f64, f64)) -> Option<usize> {
+        let layout = self.layout.get()?;
+        Some(index_at(
+            layout,
+            &self.line,
+            pos.0 - self.x + self.scroll,
+            pos.1 - self.y,
+        ))
+    }
+
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        self.marks.set_selection(selection);
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        self.marks.set_matches(matches, current);
+    }
+
+    fn get_index_pos(&self, index: usize) -> Option<(f64, f64)> {
+        let (x, y) = pos_of(self.layout.get()?, index);
+        Some((self.x - self.scroll + x, self.y + y))
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
         self.x = x;
         self.y = y;
+    }
 
-        if self.line.is_empty() && self.height != 0.0 {
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        let (layout, stale) = self.layout.update(pango, key);
+        if !stale {
             return;
         }
 
-        let w = ctx.clip_extents().2.min(theme.max_content_width);
-        pango.set_width(pango::units_from_double(w - (theme.margin * 2.0)));
+        let w = key.width.min(theme.max_content_width);
+        self.visible_width = w - (theme.margin * 2.0);
+
+        // the theme might have changed the highlighting colors, a new width
+        // doesn't
+        if self.spans_theme != Some(key.theme) {
+            self.spans = None;
+            self.spans_theme = Some(key.theme);
+        }
 
         let mut font_description = pango::FontDescription::from_string(&theme.monospace.font);
         font_description.set_size(pango::units_from_double(theme.monospace.size));
 
-        pango.set_spacing(pango::units_from_double(theme.monospace.line_spacing));
-        pango.set_alignment(Alignment::Left);
-        pango.set_wrap(WrapMode::Word); // todo don't
-        pango.set_font_description(Some(&font_description));
-        pango.set_text(&self.line);
+        layout.set_width(-1); // never wrap
+        layout.set_spacing(pango::units_from_double(theme.monospace.line_spacing));
+        layout.set_alignment(Alignment::Left);
+        layout.set_font_description(Some(&font_description));
+        layout.set_text(&self.line);
 
-        let (w, h) = pango.get_pixel_size();
-        self.width = w as f64;
+        let (w, h) = layout.get_pixel_size();
+        self.content_width = w as f64;
+        self.width = self.content_width.min(self.visible_width);
         self.height = h as f64 + theme.monospace.line_spacing;
+        self.scroll = self.scroll.min(self.max_scroll());
+
+        self.marks.invalidate();
+    }
+
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let pango = match self.layout.get() {
+            Some(layout) => layout.clone(),
+            None => return, // not measured yet
+        };
+
+        if self.marks.take_changed() {
+            let attrs = with_marks(self.get_attributes(theme), &self.marks, theme);
+            pango.set_attributes(attrs.as_ref());
+        }
+
+        let (x, y) = (self.x, self.y);
+        let visible_width = self.visible_width;
+
+        ctx.save();
+        ctx.rectangle(x, y, visible_width, self.height);
+        ctx.clip();
 
+        ctx.move_to(x - self.scroll, y);
         ctx.set_source_rgb(
             theme.monospace.color.0 as f64 / 255.0,
             theme.monospace.color.1 as f64 / 255.0,
             theme.monospace.color.2 as f64 / 255.0,
         );
-        pangocairo::show_layout(ctx, pango);
+        pangocairo::show_layout(ctx, &pango);
+
+        // fade out the clipped edges, so it's visible there's more to scroll to
+        let bg = &theme.background_color;
+        let (r, g, b) = (
+            bg.0 as f64 / 255.0,
+            bg.1 as f64 / 255.0,
+            bg.2 as f64 / 255.0,
+        );
+
+        if self.scroll > 0.0 {
+            let gradient = LinearGradient::new(x, 0.0, x + INDICATOR_WIDTH, 0.0);
+            gradient.add_color_stop_rgba(0.0, r, g, b, 1.0);
+            gradient.add_color_stop_rgba(1.0, r, g, b, 0.0);
+
+            ctx.set_source(&gradient);
+            ctx.rectangle(x, y, INDICATOR_WIDTH, self.height);
+            ctx.fill();
+        }
+
+        if self.scroll < self.max_scroll() {
+            let end = x + visible_width;
+
+            let gradient = LinearGradient::new(end - INDICATOR_WIDTH, 0.0, end, 0.0);
+            gradient.add_color_stop_rgba(0.0, r, g, b, 0.0);
+            gradient.add_color_stop_rgba(1.0, r, g, b, 1.0);
+
+            ctx.set_source(&gradient);
+            ctx.rectangle(end - INDICATOR_WIDTH, y, INDICATOR_WIDTH, self.height);
+            ctx.fill();
+        }
+
+        ctx.restore();
+    }
+
+    fn scroll(&mut self, dx: f64) -> bool {
+        let scroll = (self.scroll + dx).max(0.0).min(self.max_scroll());
+
+        if (scroll - self.scroll).abs() < f64::EPSILON {
+            return false;
+        }
 
-        ctx.rel_move_to(0.0, theme.monospace.line_spacing / 2.0);
+        self.scroll = scroll;
+        true
     }
 }
@@ -0,0 +1,179 @@
+use super::{follow, index_at, link_at, pos_of, span_attributes, with_marks, CachedLayout, Marks};
+use crate::accessible::Role;
+use crate::lines::{LayoutKey, Line, Span};
+use crate::{Data, Msg as RendererMsg, Theme};
+use cairo::Context;
+use pango::{Alignment, WrapMode};
+
+pub struct Quote {
+    line: String,
+
+    x: f64,
+    y: f64,
+
+    width: f64,
+    height: f64,
+
+    // distance between the line and its text
+    indent: f64,
+
+    layout: CachedLayout,
+    marks: Marks,
+
+    // styled ranges of the text, like bold words or links
+    spans: Vec<Span>,
+}
+
+impl Quote {
+    pub fn new(line: String) -> Self {
+        Self {
+            line,
+
+            x: 0.0,
+            y: 0.0,
+
+            width: 0.0,
+            height: 0.0,
+
+            indent: 0.0,
+
+            layout: CachedLayout::default(),
+            marks: Marks::default(),
+
+            spans: vec![],
+        }
+    }
+
+    pub fn with_spans(mut self, spans: Vec<Span>) -> Self {
+        self.spans = spans;
+        self
+    }
+}
+
+impl Line for Quote {
+    fn get_pos(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    fn get_size(&self) -> (f64, f64) {
+        (self.width, self.height)
+    }
+
+    fn get_role(&self) -> Role {
+        Role::Quote
+    }
+
+    fn get_text(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+
+    fn get_index(&self, pos: (f64, f64)) -> Option<usize> {
+        let layout = self.layout.get()?;
+        Some(index_at(
+            layout,
+            &self.line,
+            pos.0 - self.x - self.indent,
+            pos.1 - self.y,
+        ))
+    }
+
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        self.marks.set_selection(selection);
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        self.marks.set_matches(matches, current);
+    }
+
+    fn get_index_pos(&self, index: usize) -> Option<(f64, f64)> {
+        let (x, y) = pos_of(self.layout.get()?, index);
+        Some((self.x + self.indent + x, self.y + y))
+    }
+
+    fn get_link_at(&self, pos: (f64, f64)) -> Option<&str> {
+        let layout = self.layout.get()?;
+        link_at(
+            layout,
+            &self.spans,
+            pos.0 - self.x - self.indent,
+            pos.1 - self.y,
+        )
+    }
+
+    fn click_at(&mut self, data: &Data, pos: (f64, f64)) -> Option<RendererMsg> {
+        let url = self.get_link_at(pos)?.to_owned();
+        follow(data, &url)
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
+        self.x = x;
+        self.y = y;
+    }
+
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        let (layout, stale) = self.layout.update(pango, key);
+        if !stale {
+            return;
+        }
+
+        self.indent = theme.quote.indent;
+
+        let w = key.width.min(theme.max_content_width);
+        layout.set_width(pango::units_from_double(
+            w - (theme.margin * 2.0) - theme.quote.indent,
+        ));
+
+        let mut font_description = pango::FontDescription::from_string(&theme.quote.font);
+        font_description.set_size(pango::units_from_double(theme.quote.size));
+
+        layout.set_spacing(pango::units_from_double(theme.quote.line_spacing));
+        layout.set_alignment(Alignment::Left);
+        layout.set_wrap(WrapMode::Word);
+        layout.set_font_description(Some(&font_description));
+        layout.set_text(&self.line);
+        layout.set_attributes(span_attributes(&self.spans, theme).as_ref());
+
+        let (w, h) = layout.get_pixel_size();
+        self.width = w as f64 + theme.quote.indent;
+        self.height = h as f64 + theme.quote.line_spacing;
+
+        self.marks.invalidate();
+    }
+
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let pango = match self.layout.get() {
+            Some(layout) => layout.clone(),
+            None => return, // not measured yet
+        };
+
+        if self.marks.take_changed() {
+            pango.set_attributes(
+                with_marks(span_attributes(&self.spans, theme), &self.marks, theme).as_ref(),
+            );
+        }
+
+        // Draw rule
+
+        ctx.set_source_rgb(
+            theme.quote.rule_color.0 as f64 / 255.0,
+            theme.quote.rule_color.1 as f64 / 255.0,
+            theme.quote.rule_color.2 as f64 / 255.0,
+        );
+
+        ctx.rectangle(
+            self.x,
+            self.y,
+            theme.quote.rule_width,
+            self.height - theme.quote.line_spacing,
+        );
+        ctx.fill();
+
+        ctx.move_to(self.x + self.indent, self.y);
+        ctx.set_source_rgb(
+            theme.quote.color.0 as f64 / 255.0,
+            theme.quote.color.1 as f64 / 255.0,
+            theme.quote.color.2 as f64 / 255.0,
+        );
+        pangocairo::show_layout(ctx, &pango);
+    }
+}
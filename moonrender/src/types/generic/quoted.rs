@@ -0,0 +1,146 @@
+use crate::accessible::Role;
+use crate::lines::{LayoutKey, Line};
+use crate::types::generic::DecodedImage;
+use crate::{Data, Msg as RendererMsg, Theme};
+use anyhow::Result;
+use cairo::Context;
+use std::time::Duration;
+
+// a line inside block quotes, like a list item or a nested quote. it's moved
+// right and gets a rule for each quote it's in
+pub struct Quoted {
+    line: Box<dyn Line>,
+    depth: usize,
+
+    x: f64,
+    y: f64,
+
+    // distance between the rules and the line
+    indent: f64,
+}
+
+impl Quoted {
+    pub fn new(line: Box<dyn Line>, depth: usize) -> Self {
+        Self {
+            line,
+            depth,
+
+            x: 0.0,
+            y: 0.0,
+
+            indent: 0.0,
+        }
+    }
+}
+
+impl Line for Quoted {
+    fn get_pos(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    fn get_size(&self) -> (f64, f64) {
+        let (width, height) = self.line.get_size();
+        (width + self.indent, height)
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
+        self.x = x;
+        self.y = y;
+        self.line.set_pos((x + self.indent, y));
+    }
+
+    // the line is laid out as if the page was narrower by the indent, lines
+    // take the width the same way so it ends where the others do
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        self.indent = self.depth as f64 * theme.quote.indent;
+
+        let key = LayoutKey {
+            width: key.width.min(theme.max_content_width) - self.indent,
+            ..key
+        };
+
+        self.line.measure(pango, theme, key);
+    }
+
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let (_, height) = self.line.get_size();
+
+        ctx.set_source_rgb(
+            theme.quote.rule_color.0 as f64 / 255.0,
+            theme.quote.rule_color.1 as f64 / 255.0,
+            theme.quote.rule_color.2 as f64 / 255.0,
+        );
+
+        for depth in 0..self.depth {
+            let x = self.x + depth as f64 * theme.quote.indent;
+            ctx.rectangle(x, self.y, theme.quote.rule_width, height);
+        }
+        ctx.fill();
+
+        self.line.draw(ctx, theme);
+    }
+
+    fn get_role(&self) -> Role {
+        self.line.get_role()
+    }
+
+    fn get_tooltip(&self, data: &Data) -> Option<String> {
+        self.line.get_tooltip(data)
+    }
+
+    fn click(&mut self, data: &Data) -> Option<RendererMsg> {
+        self.line.click(data)
+    }
+
+    fn get_link_at(&self, pos: (f64, f64)) -> Option<&str> {
+        self.line.get_link_at(pos)
+    }
+
+    fn click_at(&mut self, data: &Data, pos: (f64, f64)) -> Option<RendererMsg> {
+        self.line.click_at(data, pos)
+    }
+
+    fn scroll(&mut self, dx: f64) -> bool {
+        self.line.scroll(dx)
+    }
+
+    fn get_text(&self) -> Option<&str> {
+        self.line.get_text()
+    }
+
+    fn get_index(&self, pos: (f64, f64)) -> Option<usize> {
+        self.line.get_index(pos)
+    }
+
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        self.line.set_selection(selection);
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        self.line.set_matches(matches, current);
+    }
+
+    fn is_focusable(&self) -> bool {
+        self.line.is_focusable()
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.line.set_focused(focused);
+    }
+
+    fn get_index_pos(&self, index: usize) -> Option<(f64, f64)> {
+        self.line.get_index_pos(index)
+    }
+
+    fn animate(&mut self, elapsed: Duration) -> Option<Duration> {
+        self.line.animate(elapsed)
+    }
+
+    fn take_request(&mut self) -> Option<String> {
+        self.line.take_request()
+    }
+
+    fn set_content(&mut self, content: Result<DecodedImage>) {
+        self.line.set_content(content);
+    }
+}
@@ -0,0 +1,301 @@
+use super::{follow, link_at, span_attributes, CachedLayout};
+use crate::lines::{LayoutKey, Line, Span};
+use crate::{Data, Msg as RendererMsg, Theme};
+use cairo::Context;
+use pango::{Alignment, WrapMode};
+
+// space between a cell's border and its text
+const CELL_PADDING: f64 = 6.0;
+
+struct Cell {
+    text: String,
+    layout: CachedLayout,
+    // styled ranges of the text, like links
+    spans: Vec<Span>,
+}
+
+pub struct Table {
+    rows: Vec<Vec<Cell>>,
+    // whether the first row is the header, drawn bold
+    header: bool,
+    // cells separated by tabs and rows by newlines, for finding and copying
+    text: String,
+
+    x: f64,
+    y: f64,
+
+    width: f64,
+    height: f64,
+
+    column_widths: Vec<f64>,
+    row_heights: Vec<f64>,
+    // what the cells were last laid out for
+    key: Option<LayoutKey>,
+}
+
+impl Table {
+    pub fn new(rows: Vec<Vec<String>>, header: bool) -> Self {
+        let text = rows
+            .iter()
+            .map(|row| row.join("\t"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|text| Cell {
+                        text,
+                        layout: CachedLayout::default(),
+                        spans: vec![],
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            rows,
+            header,
+            text,
+
+            x: 0.0,
+            y: 0.0,
+
+            width: 0.0,
+            height: 0.0,
+
+            column_widths: vec![],
+            row_heights: vec![],
+            key: None,
+        }
+    }
+
+    // spans of each cell, in the same shape as the rows
+    pub fn with_spans(mut self, spans: Vec<Vec<Vec<Span>>>) -> Self {
+        for (row, row_spans) in self.rows.iter_mut().zip(spans) {
+            for (cell, cell_spans) in row.iter_mut().zip(row_spans) {
+                cell.spans = cell_spans;
+            }
+        }
+
+        self
+    }
+
+    // the cell at the position in page coordinates, and the position
+    // relative to its text
+    fn cell_at(&self, (x, y): (f64, f64)) -> Option<(&Cell, (f64, f64))> {
+        let mut top = self.y;
+        for (row, row_height) in self.rows.iter().zip(&self.row_heights) {
+            if y < top + row_height {
+                let mut left = self.x;
+                for (column_width, cell) in self.column_widths.iter().zip(row) {
+                    if x < left + column_width {
+                        let pos = (x - left - CELL_PADDING, y - top - CELL_PADDING);
+                        return Some((cell, pos));
+                    }
+
+                    left += column_width;
+                }
+
+                return None;
+            }
+
+            top += row_height;
+        }
+
+        None
+    }
+}
+
+impl Line for Table {
+    fn get_pos(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    fn get_size(&self) -> (f64, f64) {
+        (self.width, self.height)
+    }
+
+    fn get_text(&self) -> Option<&str> {
+        Some(&self.text)
+    }
+
+    // the start of the cell under pos, so selections cover whole cells
+    fn get_index(&self, (x, y): (f64, f64)) -> Option<usize> {
+        if y < self.y {
+            return Some(0);
+        }
+
+        let mut start = 0;
+        let mut top = self.y;
+        for (row, row_height) in self.rows.iter().zip(&self.row_heights) {
+            if y < top + row_height {
+                let mut left = self.x;
+                for (column_width, cell) in self.column_widths.iter().zip(row) {
+                    if x < left + column_width {
+                        return Some(start);
+                    }
+
+                    left += column_width;
+                    start += cell.text.len() + 1;
+                }
+
+                // past the last cell, before the newline
+                return Some(start.saturating_sub(1));
+            }
+
+            start += row.iter().map(|cell| cell.text.len() + 1).sum::<usize>();
+            top += row_height;
+        }
+
+        Some(self.text.len())
+    }
+
+    fn get_link_at(&self, pos: (f64, f64)) -> Option<&str> {
+        let (cell, (x, y)) = self.cell_at(pos)?;
+        link_at(cell.layout.get()?, &cell.spans, x, y)
+    }
+
+    fn click_at(&mut self, data: &Data, pos: (f64, f64)) -> Option<RendererMsg> {
+        let url = self.get_link_at(pos)?.to_owned();
+        follow(data, &url)
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
+        self.x = x;
+        self.y = y;
+    }
+
+    // columns get as wide as their widest cell, and are narrowed by the same
+    // ratio when that's wider than the page
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut natural = vec![0.0_f64; columns];
+
+        for (i, row) in self.rows.iter_mut().enumerate() {
+            let mut font_description = pango::FontDescription::from_string(&theme.content.font);
+            font_description.set_size(pango::units_from_double(theme.content.size));
+
+            if self.header && i == 0 {
+                font_description.set_weight(pango::Weight::Bold);
+            }
+
+            for (column, cell) in row.iter_mut().enumerate() {
+                let (layout, _) = cell.layout.update(pango, key);
+
+                layout.set_width(-1);
+                layout.set_spacing(pango::units_from_double(theme.content.line_spacing));
+                layout.set_alignment(Alignment::Left);
+                layout.set_wrap(WrapMode::WordChar);
+                layout.set_font_description(Some(&font_description));
+                layout.set_text(&cell.text);
+                layout.set_attributes(span_attributes(&cell.spans, theme).as_ref());
+
+                let (w, _) = layout.get_pixel_size();
+                natural[column] = natural[column].max(w as f64 + CELL_PADDING * 2.0);
+            }
+        }
+
+        let available = key.width.min(theme.max_content_width) - (theme.margin * 2.0);
+        let total: f64 = natural.iter().sum();
+        let ratio = if total > available {
+            available / total
+        } else {
+            1.0
+        };
+
+        self.column_widths = natural.iter().map(|w| w * ratio).collect();
+        self.row_heights.clear();
+
+        for row in &self.rows {
+            let mut row_height = 0.0_f64;
+
+            for (column, cell) in row.iter().enumerate() {
+                if let Some(layout) = cell.layout.get() {
+                    let w = self.column_widths[column] - CELL_PADDING * 2.0;
+                    layout.set_width(pango::units_from_double(w.max(1.0)));
+
+                    let (_, h) = layout.get_pixel_size();
+                    row_height = row_height.max(h as f64 + CELL_PADDING * 2.0);
+                }
+            }
+
+            self.row_heights.push(row_height);
+        }
+
+        self.width = self.column_widths.iter().sum();
+        self.height = self.row_heights.iter().sum::<f64>() + theme.content.line_spacing;
+    }
+
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let mut y = self.y;
+
+        for (row, row_height) in self.rows.iter().zip(&self.row_heights) {
+            let mut x = self.x;
+
+            for (column_width, cell) in self.column_widths.iter().zip(row) {
+                if let Some(layout) = cell.layout.get() {
+                    ctx.move_to(x + CELL_PADDING, y + CELL_PADDING);
+                    ctx.set_source_rgb(
+                        theme.content.color.0 as f64 / 255.0,
+                        theme.content.color.1 as f64 / 255.0,
+                        theme.content.color.2 as f64 / 255.0,
+                    );
+                    pangocairo::show_layout(ctx, layout);
+                }
+
+                ctx.set_source_rgb(
+                    theme.quote.rule_color.0 as f64 / 255.0,
+                    theme.quote.rule_color.1 as f64 / 255.0,
+                    theme.quote.rule_color.2 as f64 / 255.0,
+                );
+                ctx.set_line_width(1.0);
+                ctx.rectangle(x + 0.5, y + 0.5, *column_width, *row_height);
+                ctx.stroke();
+
+                x += column_width;
+            }
+
+            y += row_height;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text() {
+        let rows = vec![
+            vec!["a".to_owned(), "b".to_owned()],
+            vec!["1".to_owned(), "".to_owned()],
+        ];
+        assert_eq!(Table::new(rows, true).get_text(), Some("a\tb\n1\t"));
+        assert_eq!(Table::new(vec![], false).get_text(), Some(""));
+    }
+
+    #[test]
+    fn index() {
+        let rows = vec![
+            vec!["ab".to_owned(), "c".to_owned()],
+            vec!["d".to_owned(), "ef".to_owned()],
+        ];
+        let mut table = Table::new(rows, false);
+        table.column_widths = vec![10.0, 10.0];
+        table.row_heights = vec![10.0, 10.0];
+
+        assert_eq!(table.get_index((5.0, -1.0)), Some(0));
+        assert_eq!(table.get_index((15.0, 5.0)), Some(3));
+        assert_eq!(table.get_index((25.0, 5.0)), Some(4));
+        assert_eq!(table.get_index((5.0, 15.0)), Some(5));
+        assert_eq!(table.get_index((15.0, 15.0)), Some(7));
+        assert_eq!(table.get_index((5.0, 25.0)), Some(9));
+    }
+}
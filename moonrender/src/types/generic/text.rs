@@ -1,7 +1,8 @@
-use crate::lines::Line;
-use crate::Theme;
+use super::{follow, index_at, link_at, pos_of, span_attributes, with_marks, CachedLayout, Marks};
+use crate::lines::{LayoutKey, Line, Span};
+use crate::{Data, Msg as RendererMsg, Theme};
 use cairo::Context;
-use pango::{Alignment, Layout, WrapMode};
+use pango::{Alignment, WrapMode};
 
 pub struct Text {
     line: String,
@@ -11,6 +12,12 @@ pub struct Text {
 
     width: f64,
     height: f64,
+
+    layout: CachedLayout,
+    marks: Marks,
+
+    // styled ranges of the text, like bold words or links
+    spans: Vec<Span>,
 }
 
 impl Text {
@@ -23,8 +30,18 @@ impl Text {
 
             width: 0.0,
             height: 0.0,
+
+            layout: CachedLayout::default(),
+            marks: Marks::default(),
+
+            spans: vec![],
         }
     }
+
+    pub fn with_spans(mut self, spans: Vec<Span>) -> Self {
+        self.spans = spans;
+        self
+    }
 }
 
 impl Line for Text {
@@ -36,38 +53,87 @@ impl Line for Text {
         (self.width, self.height)
     }
 
-    fn draw(&mut self, ctx: &Context, pango: &Layout, theme: &Theme) {
-        let (x, y) = ctx.get_current_point();
+    fn get_text(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+
+    fn get_index(&self, pos: (f64, f64)) -> Option<usize> {
+        let layout = self.layout.get()?;
+        Some(index_at(layout, &self.line, pos.0 - self.x, pos.1 - self.y))
+    }
+
+    fn set_selection(&mut self, selection: Option<(usize, usize)>) {
+        self.marks.set_selection(selection);
+    }
+
+    fn set_matches(&mut self, matches: Vec<(usize, usize)>, current: Option<usize>) {
+        self.marks.set_matches(matches, current);
+    }
+
+    fn get_index_pos(&self, index: usize) -> Option<(f64, f64)> {
+        let (x, y) = pos_of(self.layout.get()?, index);
+        Some((self.x + x, self.y + y))
+    }
+
+    fn get_link_at(&self, pos: (f64, f64)) -> Option<&str> {
+        let layout = self.layout.get()?;
+        link_at(layout, &self.spans, pos.0 - self.x, pos.1 - self.y)
+    }
+
+    fn click_at(&mut self, data: &Data, pos: (f64, f64)) -> Option<RendererMsg> {
+        let url = self.get_link_at(pos)?.to_owned();
+        follow(data, &url)
+    }
+
+    fn set_pos(&mut self, (x, y): (f64, f64)) {
         self.x = x;
         self.y = y;
+    }
 
-        if self.line.is_empty() && self.height != 0.0 {
+    fn measure(&mut self, pango: &pango::Context, theme: &Theme, key: LayoutKey) {
+        let (layout, stale) = self.layout.update(pango, key);
+        if !stale {
             return;
         }
 
-        let w = ctx.clip_extents().2.min(theme.max_content_width);
-        pango.set_width(pango::units_from_double(w - (theme.margin * 2.0)));
+        let w = key.width.min(theme.max_content_width);
+        layout.set_width(pango::units_from_double(w - (theme.margin * 2.0)));
 
         let mut font_description = pango::FontDescription::from_string(&theme.content.font);
         font_description.set_size(pango::units_from_double(theme.content.size));
 
-        pango.set_spacing(pango::units_from_double(theme.content.line_spacing));
-        pango.set_alignment(Alignment::Left);
-        pango.set_wrap(WrapMode::Word);
-        pango.set_font_description(Some(&font_description));
-        pango.set_text(&self.line);
+        layout.set_spacing(pango::units_from_double(theme.content.line_spacing));
+        layout.set_alignment(Alignment::Left);
+        layout.set_wrap(WrapMode::Word);
+        layout.set_font_description(Some(&font_description));
+        layout.set_text(&self.line);
+        layout.set_attributes(span_attributes(&self.spans, theme).as_ref());
 
-        let (w, h) = pango.get_pixel_size();
+        let (w, h) = layout.get_pixel_size();
         self.width = w as f64;
         self.height = h as f64 + theme.content.line_spacing;
 
+        self.marks.invalidate();
+    }
+
+    fn draw(&mut self, ctx: &Context, theme: &Theme) {
+        let pango = match self.layout.get() {
+            Some(layout) => layout.clone(),
+            None => return, // not measured yet
+        };
+
+        if self.marks.take_changed() {
+            pango.set_attributes(
+                with_marks(span_attributes(&self.spans, theme), &self.marks, theme).as_ref(),
+            );
+        }
+
+        ctx.move_to(self.x, self.y);
         ctx.set_source_rgb(
             theme.content.color.0 as f64 / 255.0,
             theme.content.color.1 as f64 / 255.0,
             theme.content.color.2 as f64 / 255.0,
         );
-        pangocairo::show_layout(ctx, pango);
-
-        ctx.rel_move_to(0.0, theme.content.line_spacing / 2.0);
+        pangocairo::show_layout(ctx, &pango);
     }
 }
@@ -0,0 +1,26 @@
+use super::{generic::Image, Line, Renderer};
+use anyhow::Result;
+
+// whole page images, decoded once all of the body is there
+pub struct ImageViewer;
+
+impl ImageViewer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Renderer for ImageViewer {
+    // never called, see is_binary
+    fn parse_line(&mut self, _line: &str) -> Result<Vec<Box<dyn Line>>> {
+        Ok(vec![])
+    }
+
+    fn is_binary(&self) -> bool {
+        true
+    }
+
+    fn finish_bytes(&mut self, body: &[u8]) -> Result<Vec<Box<dyn Line>>> {
+        Ok(vec![Box::new(Image::decode(body)?)])
+    }
+}
@@ -2,9 +2,40 @@ use super::lines::Line;
 use anyhow::Result;
 
 pub mod generic;
-pub mod text_gemini;
-pub mod text_plain;
+pub(crate) mod image;
+pub(crate) mod text_gemini;
+pub(crate) mod text_markdown;
+pub(crate) mod text_plain;
 
+// turns a page into lines, one source line at a time. register an
+// implementation with Renderer::register to support a new mime type. a
+// renderer is reused between pages, reset is called before each one
 pub trait Renderer {
-    fn parse_line(&mut self, line: &str) -> Result<Box<dyn Line>>;
+    fn parse_line(&mut self, line: &str) -> Result<Vec<Box<dyn Line>>>;
+
+    // called after the last line, for lines that were held back
+    fn finish(&mut self) -> Result<Vec<Box<dyn Line>>> {
+        Ok(vec![]) // implementation optional
+    }
+
+    fn reset(&mut self) {
+        // implementation optional
+    }
+
+    // renderers for binary formats, like images, get the raw body through
+    // parse_bytes instead of parse_line
+    fn is_binary(&self) -> bool {
+        false // implementation optional
+    }
+
+    fn parse_bytes(&mut self, _bytes: &[u8]) -> Result<Vec<Box<dyn Line>>> {
+        Ok(vec![]) // implementation optional
+    }
+
+    // called before finish with the whole body, for binary formats that
+    // can't be shown until all of it is there. the renderer keeps the body
+    // already, so there's no need to collect it in parse_bytes
+    fn finish_bytes(&mut self, _body: &[u8]) -> Result<Vec<Box<dyn Line>>> {
+        Ok(vec![]) // implementation optional
+    }
 }
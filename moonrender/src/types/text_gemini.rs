@@ -1,66 +1,57 @@
 use super::{
-    generic::{Heading, Link, List, Preformat, Text},
+    generic::{Heading, Link, List, Preformat, Quote, Text},
     Line, Renderer,
 };
-use anyhow::{Context as _, Result};
+use anyhow::Result;
+use gemtext::{Line as Gemtext, Parser};
 
 pub struct Gemini {
-    is_preformatted: bool,
+    parser: Parser,
 }
 
 impl Gemini {
     pub fn new() -> Self {
         Self {
-            is_preformatted: false,
+            parser: Parser::new(),
         }
     }
 }
 
-impl Renderer for Gemini {
-    fn parse_line(&mut self, line: &str) -> Result<Box<dyn Line>> {
-        let mut line = line.to_owned();
-
-        if line.starts_with("```") {
-            self.is_preformatted = !self.is_preformatted;
-            line = String::new();
+fn to_lines(line: Gemtext) -> Vec<Box<dyn Line>> {
+    match line {
+        Gemtext::Text(text) => vec![Box::new(Text::new(text))],
+        Gemtext::Link { url, label } => {
+            vec![Box::new(Link::new(
+                label.unwrap_or_else(|| url.clone()),
+                url,
+            ))]
         }
-
-        if self.is_preformatted {
-            return Ok(Box::new(Preformat::new(line)));
+        Gemtext::Heading { level, text } => {
+            log::debug!("{} heading {}", level, text);
+            vec![Box::new(Heading::new(text, level))]
         }
+        Gemtext::ListItem(text) => vec![Box::new(List::new(text))],
+        Gemtext::Quote(text) => vec![Box::new(Quote::new(text))],
+        Gemtext::Preformatted { alt, lines } => {
+            vec![Box::new(Preformat::new(lines.join("\n"), alt))]
+        }
+    }
+}
 
-        line = line.trim().to_owned();
-        if line.starts_with("=>") {
-            let data = &mut line[2..].trim().split_whitespace();
-
-            let link = data.next().context("No link?")?;
-            let mut caption = data.collect::<Vec<&str>>().join(" ");
-
-            if caption.is_empty() {
-                caption = link.to_owned();
-            }
-
-            Ok(Box::new(Link::new(caption, link.to_owned())))
-        } else if line.starts_with('*') {
-            Ok(Box::new(List::new(line[1..].trim().to_owned())))
-        } else if line.starts_with('#') {
-            let mut heading = 0;
-            let mut iter = line.chars();
-
-            while let Some(chr) = iter.next() {
-                if chr == '#' {
-                    heading += 1;
-                } else {
-                    break;
-                }
-            }
+impl Renderer for Gemini {
+    fn parse_line(&mut self, line: &str) -> Result<Vec<Box<dyn Line>>> {
+        Ok(self
+            .parser
+            .parse_line(line)
+            .map(to_lines)
+            .unwrap_or_default())
+    }
 
-            let line = iter.collect::<String>();
+    fn finish(&mut self) -> Result<Vec<Box<dyn Line>>> {
+        Ok(self.parser.finish().map(to_lines).unwrap_or_default())
+    }
 
-            log::debug!("{} heading {}", heading, line);
-            Ok(Box::new(Heading::new(line, heading)))
-        } else {
-            Ok(Box::new(Text::new(line)))
-        }
+    fn reset(&mut self) {
+        self.parser = Parser::new();
     }
 }
@@ -0,0 +1,811 @@
+use super::{
+    generic::{Heading, Link, List, Preformat, Quote, Quoted, Table, Text},
+    Line, Renderer,
+};
+use crate::lines::Span;
+use anyhow::Result;
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Options, Parser, Tag};
+
+// markdown is parsed a few top level blocks at a time, so the page shows up
+// while it loads
+pub struct Markdown {
+    blocks: Blocks,
+    // blocks with links to references that aren't defined yet, see
+    // has_missing_references. they're parsed again with the next blocks
+    // once there are more definitions, and once the page is complete
+    held: String,
+    // length of the definitions when the held blocks were last parsed
+    waiting: Option<usize>,
+}
+
+impl Markdown {
+    pub fn new() -> Self {
+        Self {
+            blocks: Blocks::default(),
+            held: String::new(),
+            waiting: None,
+        }
+    }
+
+    // lines of the held blocks, unless some of their links are still broken
+    fn parse(&mut self, last: bool) -> Vec<Box<dyn Line>> {
+        // definitions can come after the links that use them, so they're
+        // given to every part of the page
+        let source = format!("{}\n\n{}", self.blocks.definitions, self.held);
+
+        if !last && has_missing_references(&source) {
+            self.waiting = Some(self.blocks.definitions.len());
+            return vec![];
+        }
+
+        let mut builder = Builder::default();
+        for event in Parser::new_ext(&source, Options::ENABLE_TABLES) {
+            builder.push(event);
+        }
+        builder.flush();
+
+        self.held.clear();
+        self.waiting = None;
+        builder.lines
+    }
+}
+
+impl Renderer for Markdown {
+    fn parse_line(&mut self, line: &str) -> Result<Vec<Box<dyn Line>>> {
+        match self.blocks.push(line) {
+            Some(blocks) => {
+                self.held.push_str(&blocks);
+
+                // nothing held can resolve until another definition comes
+                if self.waiting == Some(self.blocks.definitions.len()) {
+                    return Ok(vec![]);
+                }
+
+                Ok(self.parse(false))
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<Box<dyn Line>>> {
+        let rest = self.blocks.take();
+        self.held.push_str(&rest);
+
+        let lines = self.parse(true);
+        self.reset();
+
+        Ok(lines)
+    }
+
+    fn reset(&mut self) {
+        self.blocks = Blocks::default();
+        self.held.clear();
+        self.waiting = None;
+    }
+}
+
+// whether links like [text][label] or [label][] use labels that aren't
+// defined, so they might be further down the page. a [label] alone isn't
+// waited for, it's as often just text in brackets like [1] or [WIP]
+fn has_missing_references(source: &str) -> bool {
+    // the callback gives broken links somewhere to go, so they come out as
+    // links of an unknown type
+    let callback = |_: &str, _: &str| Some((String::new(), String::new()));
+    let mut parser =
+        Parser::new_with_broken_link_callback(source, Options::ENABLE_TABLES, Some(&callback));
+
+    parser.any(|event| match event {
+        Event::Start(Tag::Link(link_type, _, _)) | Event::Start(Tag::Image(link_type, _, _)) => {
+            matches!(
+                link_type,
+                LinkType::ReferenceUnknown | LinkType::CollapsedUnknown
+            )
+        }
+        _ => false,
+    })
+}
+
+// a block that goes on until a closing line, even over blank lines
+enum Open {
+    // a fenced code block, until a line starting with its fence
+    Code(String),
+    // html like comments, until a line containing the end
+    Html(&'static str),
+}
+
+// html blocks that can contain blank lines, and what ends them
+const HTML_BLOCKS: &[(&str, &str)] = &[
+    ("<!--", "-->"),
+    ("<pre", "</pre>"),
+    ("<script", "</script>"),
+    ("<style", "</style>"),
+];
+
+// splits the source into whole top level blocks. a block ends at a blank
+// line followed by an unindented line that doesn't continue a list
+#[derive(Default)]
+struct Blocks {
+    pending: String,
+    // reference definitions seen so far
+    definitions: String,
+
+    open: Option<Open>,
+    // whether the last line was blank
+    blank: bool,
+}
+
+impl Blocks {
+    // adds a line, and returns the blocks before it if it starts a new one
+    fn push(&mut self, line: &str) -> Option<String> {
+        let trimmed = line.trim_start();
+        let indented = line.len() - trimmed.len() >= 4;
+
+        let closed = match &self.open {
+            Some(Open::Code(fence)) => !indented && trimmed.starts_with(fence.as_str()),
+            Some(Open::Html(end)) => line.contains(end),
+            None => false,
+        };
+
+        if self.open.is_some() {
+            if closed {
+                self.open = None;
+            }
+
+            self.add(line);
+            return None;
+        }
+
+        let starts_block = self.blank
+            && line == trimmed
+            && !trimmed.is_empty()
+            && !is_list_item(trimmed)
+            && !self.pending.trim().is_empty();
+
+        let blocks = if starts_block {
+            Some(std::mem::take(&mut self.pending))
+        } else {
+            None
+        };
+
+        if !indented {
+            self.open = open_block(trimmed);
+
+            if is_definition(trimmed) {
+                self.definitions.push_str(line);
+                self.definitions.push('\n');
+            }
+        }
+
+        self.add(line);
+        blocks
+    }
+
+    fn add(&mut self, line: &str) {
+        self.pending.push_str(line);
+        self.pending.push('\n');
+        self.blank = line.trim().is_empty();
+    }
+
+    // whatever is left, once the source is complete
+    fn take(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+// the block the line opens, if it can contain blank lines
+fn open_block(line: &str) -> Option<Open> {
+    for fence in &["```", "~~~"] {
+        if line.starts_with(fence) {
+            let c = fence.chars().next().unwrap_or_default();
+            let fence: String = line.chars().take_while(|&l| l == c).collect();
+
+            return Some(Open::Code(fence));
+        }
+    }
+
+    let lowercase = line.to_ascii_lowercase();
+    HTML_BLOCKS
+        .iter()
+        .find(|(start, end)| lowercase.starts_with(start) && !lowercase.contains(end))
+        .map(|(_, end)| Open::Html(end))
+}
+
+fn is_list_item(line: &str) -> bool {
+    let bullets = ["- ", "* ", "+ "];
+    if bullets.iter().any(|bullet| line.starts_with(bullet)) {
+        return true;
+    }
+
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    rest.len() < line.len() && (rest.starts_with(". ") || rest.starts_with(") "))
+}
+
+// like [label]: url
+fn is_definition(line: &str) -> bool {
+    line.starts_with('[')
+        && line
+            .find("]:")
+            .map_or(false, |end| end > 1 && !line[..end].contains(']'))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Emphasis,
+    Strong,
+    Code,
+    Strikethrough,
+    Link,
+}
+
+// turns parser events into lines, a block at a time
+#[derive(Default)]
+struct Builder {
+    lines: Vec<Box<dyn Line>>,
+
+    // text of the current paragraph, heading, list item or table cell
+    text: String,
+    spans: Vec<Span>,
+    // styles that aren't closed yet, and where they start in text
+    open: Vec<(Style, usize)>,
+    // targets of the links that aren't closed yet
+    link_urls: Vec<String>,
+
+    heading: Option<u8>,
+    quote_depth: usize,
+
+    // the next number of each list the current item is in, None for
+    // unordered lists
+    lists: Vec<Option<u64>>,
+    // bullet for the first block of the current item, the rest have none
+    bullet: Option<Option<String>>,
+
+    // text and info string of the current code block
+    code: Option<(String, Option<String>)>,
+
+    table: Vec<Vec<String>>,
+    row: Vec<String>,
+    // spans of the cells, in the same shape
+    table_spans: Vec<Vec<Vec<Span>>>,
+    row_spans: Vec<Vec<Span>>,
+
+    // how deep the text is in paragraphs, headings, list items and table
+    // cells. html outside of them is a block of its own
+    inline: usize,
+    html_block: bool,
+    // an html tag that isn't closed yet
+    tag: Option<String>,
+}
+
+impl Builder {
+    fn push(&mut self, event: Event) {
+        // html blocks end with the first thing that isn't html
+        if self.html_block && !matches!(event, Event::Html(_)) {
+            self.html_block = false;
+            self.flush();
+        }
+
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+
+            Event::Text(text) => match &mut self.code {
+                Some((code, _)) => code.push_str(&text),
+                None => self.text.push_str(&text),
+            },
+            Event::Code(code) => {
+                self.open.push((Style::Code, self.text.len()));
+                self.text.push_str(&code);
+                self.close(Style::Code);
+            }
+            Event::Html(html) => {
+                if self.inline == 0 {
+                    self.html_block = true;
+                }
+
+                self.push_html(&html);
+            }
+
+            Event::SoftBreak => self.text.push(' '),
+            Event::HardBreak => self.text.push('\n'),
+            Event::Rule => self.flush(),
+
+            Event::FootnoteReference(name) => self.text.push_str(&format!("[{}]", name)),
+            Event::TaskListMarker(done) => self.text.push_str(if done { "☑ " } else { "☐ " }),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            // html blocks before it in the same item are lines of their own
+            Tag::Paragraph => {
+                self.flush();
+                self.inline += 1;
+            }
+            Tag::TableCell => self.inline += 1,
+            Tag::Heading(level) => {
+                self.inline += 1;
+                self.heading = Some(level.min(6) as u8);
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) if !info.is_empty() => Some(info.to_string()),
+                    _ => None,
+                };
+
+                self.code = Some((String::new(), info));
+            }
+
+            // the text of a tight item ends where its nested list starts
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                self.inline += 1;
+
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        Some(format!("{}.", *number - 1))
+                    }
+                    _ => None,
+                };
+
+                self.bullet = Some(bullet);
+            }
+
+            Tag::TableHead | Tag::TableRow => {
+                self.row.clear();
+                self.row_spans.clear();
+            }
+
+            Tag::Emphasis => self.open.push((Style::Emphasis, self.text.len())),
+            Tag::Strong => self.open.push((Style::Strong, self.text.len())),
+            Tag::Strikethrough => self.open.push((Style::Strikethrough, self.text.len())),
+            Tag::Link(_, url, _) | Tag::Image(_, url, _) => {
+                self.open.push((Style::Link, self.text.len()));
+                self.link_urls.push(url.to_string());
+            }
+
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {
+                self.inline -= 1;
+                self.flush();
+            }
+            Tag::Item => {
+                self.inline -= 1;
+                self.flush();
+            }
+            Tag::Heading(_) => {
+                self.inline -= 1;
+                self.flush();
+                self.heading = None;
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quote_depth -= 1;
+            }
+            Tag::CodeBlock(_) => {
+                if let Some((code, info)) = self.code.take() {
+                    let code = code.trim_end_matches('\n').to_owned();
+                    self.add(Box::new(Preformat::new(code, info)), self.quote_depth);
+                }
+            }
+            Tag::List(_) => {
+                self.flush();
+                self.lists.pop();
+            }
+
+            // cells are trimmed, their spans move along
+            Tag::TableCell => {
+                self.inline -= 1;
+
+                let text = std::mem::take(&mut self.text);
+                let spans = std::mem::take(&mut self.spans);
+                let (cell, spans) = trim_spans(&text, spans);
+
+                self.row.push(cell);
+                self.row_spans.push(spans);
+            }
+            Tag::TableHead | Tag::TableRow => {
+                self.table.push(std::mem::take(&mut self.row));
+                self.table_spans.push(std::mem::take(&mut self.row_spans));
+            }
+            Tag::Table(_) => {
+                let rows = std::mem::take(&mut self.table);
+                let spans = std::mem::take(&mut self.table_spans);
+
+                let table = Table::new(rows, true).with_spans(spans);
+                self.add(Box::new(table), self.quote_depth);
+            }
+
+            Tag::Emphasis => {
+                self.close(Style::Emphasis);
+            }
+            Tag::Strong => {
+                self.close(Style::Strong);
+            }
+            Tag::Strikethrough => {
+                self.close(Style::Strikethrough);
+            }
+            Tag::Link(_, _, _) | Tag::Image(_, _, _) => {
+                self.close(Style::Link);
+            }
+
+            _ => {}
+        }
+    }
+
+    // ends the last open span of the style
+    fn close(&mut self, style: Style) {
+        let i = match self.open.iter().rposition(|(open, _)| *open == style) {
+            Some(i) => i,
+            None => return,
+        };
+
+        let (_, start) = self.open.remove(i);
+        let mut span = Span {
+            start,
+            ..Span::default()
+        };
+
+        match style {
+            Style::Emphasis => span.italic = true,
+            Style::Strong => span.bold = true,
+            Style::Code => span.monospace = true,
+            Style::Strikethrough => span.strikethrough = true,
+            Style::Link => {
+                let url = self.link_urls.pop().unwrap_or_default();
+
+                // links and images without a label are shown by their url
+                if start == self.text.len() {
+                    self.text.push_str(&url);
+                }
+
+                span.link = Some(url);
+            }
+        }
+
+        span.end = self.text.len();
+        self.spans.push(span);
+    }
+
+    // adds the text of html without its tags, line breaks are kept
+    fn push_html(&mut self, html: &str) {
+        for c in html.chars() {
+            if let Some(tag) = &mut self.tag {
+                tag.push(c);
+
+                let closed = if tag.starts_with("<!--") {
+                    tag.len() > 4 && tag.ends_with("-->")
+                } else {
+                    c == '>'
+                };
+
+                if closed {
+                    if is_line_break(tag) {
+                        self.text.push('\n');
+                    }
+                    self.tag = None;
+                }
+            } else if c == '<' {
+                self.tag = Some(c.to_string());
+            } else if c.is_whitespace() {
+                // whitespace in html collapses, like in browsers
+                if !self.text.is_empty() && !self.text.ends_with(char::is_whitespace) {
+                    self.text.push(' ');
+                }
+            } else {
+                self.text.push(c);
+            }
+        }
+    }
+
+    // adds a line, moved right of a rule for every quote it's in
+    fn add(&mut self, line: Box<dyn Line>, depth: usize) {
+        if depth == 0 {
+            self.lines.push(line);
+        } else {
+            self.lines.push(Box::new(Quoted::new(line, depth)));
+        }
+    }
+
+    // adds the text collected so far as a line, of the kind the blocks it's
+    // in call for
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let text = text.trim_end().to_owned();
+        let spans = std::mem::take(&mut self.spans);
+
+        if text.trim().is_empty() {
+            return;
+        }
+
+        // a paragraph that's only a link is a link line, which can be
+        // focused with the keyboard
+        if let [Span {
+            start: 0,
+            end,
+            link: Some(url),
+            ..
+        }] = spans.as_slice()
+        {
+            if *end >= text.len() && self.heading.is_none() && self.lists.is_empty() {
+                let link = Link::new(text, url.clone());
+                self.add(Box::new(link), self.quote_depth);
+                return;
+            }
+        }
+
+        // paragraphs of a quote draw the innermost rule themselves
+        let mut depth = self.quote_depth;
+
+        let line: Box<dyn Line> = if let Some(level) = self.heading {
+            Box::new(Heading::new(text, level).with_spans(spans))
+        } else if !self.lists.is_empty() {
+            // later blocks of an item line up with its first, without a bullet
+            let bullet = self.bullet.take().unwrap_or_else(|| Some(String::new()));
+            let depth = self.lists.len() - 1;
+
+            Box::new(List::nested(text, bullet, depth).with_spans(spans))
+        } else if self.quote_depth > 0 {
+            depth -= 1;
+            Box::new(Quote::new(text).with_spans(spans))
+        } else {
+            Box::new(Text::new(text).with_spans(spans))
+        };
+
+        self.add(line, depth);
+    }
+}
+
+// <br>, <br/> and <br />
+fn is_line_break(tag: &str) -> bool {
+    let name = tag.trim_start_matches('<').trim_end_matches('>');
+    let name = name.trim_end_matches('/').trim();
+
+    name.eq_ignore_ascii_case("br")
+}
+
+// trims the text, and moves the spans with it. spans that end up empty are
+// dropped
+fn trim_spans(text: &str, spans: Vec<Span>) -> (String, Vec<Span>) {
+    let trimmed = text.trim();
+    let start = text.len() - text.trim_start().len();
+
+    let spans = spans
+        .into_iter()
+        .map(|span| Span {
+            start: span.start.saturating_sub(start).min(trimmed.len()),
+            end: span.end.saturating_sub(start).min(trimmed.len()),
+            ..span
+        })
+        .filter(|span| span.start < span.end)
+        .collect();
+
+    (trimmed.to_owned(), spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accessible::Role;
+
+    fn render(source: &str) -> Vec<(Role, String)> {
+        let mut markdown = Markdown::new();
+        let mut lines = vec![];
+
+        for line in source.lines() {
+            lines.extend(markdown.parse_line(line).unwrap());
+        }
+        lines.extend(markdown.finish().unwrap());
+
+        lines
+            .iter()
+            .map(|line| {
+                let text = line.get_text().unwrap_or_default().to_owned();
+                (line.get_role(), text)
+            })
+            .collect()
+    }
+
+    fn text(text: &str) -> (Role, String) {
+        (Role::Text, text.to_owned())
+    }
+
+    #[test]
+    fn blocks() {
+        let source = [
+            "# Title",
+            "",
+            "para",
+            "graph",
+            "",
+            "- item",
+            "",
+            "- loose item",
+            "",
+            "    indented code",
+            "```",
+            "",
+            "fenced",
+            "```",
+            "",
+            "<!--",
+            "",
+            "-->",
+            "",
+            "end",
+        ];
+
+        let mut blocks = Blocks::default();
+        let mut chunks = vec![];
+        for line in source.iter() {
+            chunks.extend(blocks.push(line));
+        }
+        chunks.push(blocks.take());
+
+        assert_eq!(
+            chunks,
+            [
+                "# Title\n\n",
+                // lists can go on after blank lines
+                "para\ngraph\n\n- item\n\n- loose item\n\n    indented code\n```\n\nfenced\n```\n\n",
+                "<!--\n\n-->\n\n",
+                "end\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn block_starts() {
+        assert!(is_list_item("- a"));
+        assert!(is_list_item("12. a"));
+        assert!(is_list_item("3) a"));
+        assert!(!is_list_item("-a"));
+        assert!(!is_list_item("2020 was"));
+        assert!(!is_list_item(". a"));
+
+        assert!(is_definition("[label]: gemini://host/"));
+        assert!(!is_definition("[]: gemini://host/"));
+        assert!(!is_definition("[a] b]: c"));
+        assert!(!is_definition("[label] text"));
+    }
+
+    #[test]
+    fn progressive() {
+        let mut markdown = Markdown::new();
+
+        let mut parse = |line| markdown.parse_line(line).unwrap().len();
+
+        // blocks come out once the next one starts
+        assert_eq!(parse("first"), 0);
+        assert_eq!(parse(""), 0);
+        assert_eq!(parse("[link][later]"), 1);
+
+        // and are held back while their references aren't defined
+        assert_eq!(parse(""), 0);
+        assert_eq!(parse("text"), 0);
+        assert_eq!(parse(""), 0);
+        assert_eq!(parse("[later]: /target"), 2);
+
+        assert!(markdown.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn undefined_shortcuts() {
+        let mut markdown = Markdown::new();
+
+        let mut parse = |line| markdown.parse_line(line).unwrap().len();
+
+        // brackets that aren't references don't hold the next blocks back
+        assert_eq!(parse("see [1]"), 0);
+        assert_eq!(parse(""), 0);
+        assert_eq!(parse("second"), 1);
+        assert_eq!(parse(""), 0);
+        assert_eq!(parse("[WIP] third"), 1);
+        assert_eq!(parse(""), 0);
+        assert_eq!(parse("fourth"), 1);
+        assert_eq!(parse(""), 0);
+        assert_eq!(parse("fifth"), 1);
+
+        assert_eq!(markdown.finish().unwrap().len(), 1);
+
+        let lines = render("see [1]\n\n[x] y");
+        assert_eq!(lines, [text("see [1]"), text("[x] y")]);
+
+        // collapsed references wait for their definition like full ones
+        let lines = render("[a link][]\n\ntext\n\n[a link]: gemini://host/");
+        assert_eq!(lines, [(Role::Link, "a link".to_owned()), text("text")]);
+    }
+
+    #[test]
+    fn forward_references() {
+        let lines = render("[a link][ref]\n\ntext\n\n[ref]: gemini://host/");
+        assert_eq!(lines, [(Role::Link, "a link".to_owned()), text("text")]);
+    }
+
+    #[test]
+    fn html() {
+        let lines = render("a<br>b <i>c</i><br />d\n\n<div>\n  block\n  <!-- x -->\n</div>\n\ne");
+        assert_eq!(lines, [text("a\nb c\nd"), text("block"), text("e")]);
+
+        assert!(is_line_break("<br>"));
+        assert!(is_line_break("<BR/>"));
+        assert!(is_line_break("<br />"));
+        assert!(!is_line_break("<b>"));
+    }
+
+    #[test]
+    fn quotes() {
+        let lines = render("> a\n>\n> > b\n>\n> - c\n>\n> # d");
+        assert_eq!(
+            lines,
+            [
+                (Role::Quote, "a".to_owned()),
+                (Role::Quote, "b".to_owned()),
+                (Role::ListItem, "c".to_owned()),
+                (Role::Heading(1), "d".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let mut builder = Builder::default();
+        let source = "a *b* **c** `d` [e](u) [](v)";
+        for event in Parser::new(source) {
+            if let Event::End(Tag::Paragraph) = event {
+                break;
+            }
+            builder.push(event);
+        }
+
+        assert_eq!(builder.text, "a b c d e v");
+
+        let ranges: Vec<_> = builder
+            .spans
+            .iter()
+            .map(|span| (span.start, span.end, span.link.as_deref()))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                (2, 3, None),
+                (4, 5, None),
+                (6, 7, None),
+                (8, 9, Some("u")),
+                (10, 11, Some("v")),
+            ]
+        );
+
+        assert!(builder.spans[0].italic);
+        assert!(builder.spans[1].bold);
+        assert!(builder.spans[2].monospace);
+    }
+
+    #[test]
+    fn trimmed_spans() {
+        let span = |start, end| Span {
+            start,
+            end,
+            ..Span::default()
+        };
+
+        let (text, spans) = trim_spans("  ab cd  ", vec![span(2, 4), span(5, 9), span(0, 1)]);
+        assert_eq!(text, "ab cd");
+        assert_eq!(spans, [span(0, 2), span(3, 5)]);
+    }
+}
@@ -10,9 +10,9 @@ impl Plain {
 }
 
 impl Renderer for Plain {
-    fn parse_line(&mut self, line: &str) -> Result<Box<dyn Line>> {
+    fn parse_line(&mut self, line: &str) -> Result<Vec<Box<dyn Line>>> {
         let line = line.to_owned();
 
-        Ok(Box::new(Preformat::new(line)))
+        Ok(vec![Box::new(Preformat::new(line, None))])
     }
 }
@@ -0,0 +1,395 @@
+use atk::ObjectExt;
+use glib::translate::{from_glib, ToGlib, ToGlibPtr};
+use glib::{Cast, StaticType};
+use glib_sys::{gpointer, GType};
+use moonrender::{AccessibleLine, Role};
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_uint};
+use std::{mem, ptr, rc::Rc};
+
+// screen readers can't see inside a drawing area, so the page is drawn in one
+// whose accessible reports every line of the page as a child of its own.
+//
+// gtk-rs can't subclass atk objects, so the types are registered by hand
+glib_wrapper! {
+    pub struct PageArea(Object<gtk_sys::GtkDrawingArea, gtk_sys::GtkDrawingAreaClass, PageAreaClass>)
+        @extends gtk::DrawingArea, gtk::Widget, @implements gtk::Buildable;
+
+    match fn {
+        get_type => || page_area_get_type(),
+    }
+}
+
+impl PageArea {
+    pub fn new() -> Self {
+        glib::Object::new(Self::static_type(), &[])
+            .expect("cannot create page area")
+            .downcast()
+            .expect("page area has the wrong type")
+    }
+}
+
+impl Default for PageArea {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the lines of the page, shared with the page's accessible
+type Nodes = Rc<RefCell<Vec<atk::Object>>>;
+
+const NODES_KEY: &[u8] = b"moonrender-nodes\0";
+const NODE_KEY: &[u8] = b"moonrender-node\0";
+
+// what a line's accessible reports besides its role and name
+struct Node {
+    index: usize,
+    level: Option<u8>,
+    target: Option<String>,
+    focusable: bool,
+    focused: Cell<bool>,
+}
+
+#[derive(Default)]
+pub struct Tree {
+    nodes: Nodes,
+    focus: Option<usize>,
+}
+
+impl Tree {
+    pub fn len(&self) -> usize {
+        self.nodes.borrow().len()
+    }
+
+    // starts over for a new page
+    pub fn clear(&mut self, page: &atk::Object, title: &str) {
+        self.attach(page);
+
+        page.set_role(atk::Role::DocumentWeb);
+        page.set_name(title);
+
+        let nodes = mem::take(&mut *self.nodes.borrow_mut());
+        for (i, node) in nodes.iter().enumerate().rev() {
+            emit_children_changed(page, "children-changed::remove", i, node);
+        }
+
+        self.focus = None;
+    }
+
+    // adds the lines the page got since the last call
+    pub fn extend(&mut self, page: &atk::Object, lines: Vec<AccessibleLine>) {
+        self.attach(page);
+
+        for line in lines {
+            let index = self.len();
+            let node = create_node(page, index, line);
+
+            self.nodes.borrow_mut().push(node.clone());
+            emit_children_changed(page, "children-changed::add", index, &node);
+        }
+    }
+
+    // keeps the focus in sync with the renderer's, by line index
+    pub fn set_focus(&mut self, page: &atk::Object, focus: Option<usize>) {
+        if focus == self.focus {
+            return;
+        }
+
+        let focused = atk::StateType::Focused.to_glib() as atk::State;
+        let nodes = self.nodes.borrow();
+
+        if let Some(node) = self.focus.and_then(|i| nodes.get(i)) {
+            set_focused(node, false);
+            node.notify_state_change(focused, false);
+        }
+
+        if let Some(node) = focus.and_then(|i| nodes.get(i)) {
+            set_focused(node, true);
+            node.notify_state_change(focused, true);
+
+            unsafe {
+                gobject_sys::g_signal_emit_by_name(
+                    page.to_glib_none().0 as *mut _,
+                    b"active-descendant-changed\0".as_ptr() as *const c_char,
+                    node.to_glib_none().0 as gpointer,
+                );
+            }
+        }
+
+        self.focus = focus;
+    }
+
+    // gtk creates the page's accessible on its own, the first time it's asked
+    // for, so it's given the nodes then
+    fn attach(&self, page: &atk::Object) {
+        unsafe {
+            if get_data::<Nodes>(page, NODES_KEY).is_none() {
+                set_data(page, NODES_KEY, self.nodes.clone());
+            }
+        }
+    }
+}
+
+fn create_node(page: &atk::Object, index: usize, line: AccessibleLine) -> atk::Object {
+    let node = glib::Object::new(unsafe { from_glib(line_accessible_get_type()) }, &[])
+        .expect("cannot create accessible object")
+        .downcast::<atk::Object>()
+        .expect("accessible object is not an atk object");
+
+    let (role, level, target) = match line.role {
+        Role::Text => (atk::Role::Paragraph, None, None),
+        Role::Heading(level) => (atk::Role::Heading, Some(level), None),
+        Role::Link => (atk::Role::Link, None, line.description.clone()),
+        Role::ListItem => (atk::Role::ListItem, None, None),
+        Role::Quote => (atk::Role::BlockQuote, None, None),
+        Role::Preformatted => (atk::Role::Section, None, None),
+        Role::Image => (atk::Role::Image, None, None),
+    };
+
+    node.set_role(role);
+    node.set_name(&line.text);
+
+    if let Some(description) = &line.description {
+        node.set_description(description);
+    }
+
+    let data = Node {
+        index,
+        level,
+        target,
+        focusable: line.focusable,
+        focused: Cell::new(false),
+    };
+
+    unsafe {
+        set_data(&node, NODE_KEY, data);
+    }
+
+    node.set_parent(page);
+    node
+}
+
+fn set_focused(node: &atk::Object, focused: bool) {
+    if let Some(data) = unsafe { get_data::<Node>(node, NODE_KEY) } {
+        data.focused.set(focused);
+    }
+}
+
+fn emit_children_changed(page: &atk::Object, signal: &str, index: usize, node: &atk::Object) {
+    let signal = CString::new(signal).expect("signal name has a nul byte");
+
+    unsafe {
+        gobject_sys::g_signal_emit_by_name(
+            page.to_glib_none().0 as *mut _,
+            signal.as_ptr(),
+            index as c_uint,
+            node.to_glib_none().0 as gpointer,
+        );
+    }
+}
+
+unsafe fn set_data<T: 'static>(object: &atk::Object, key: &[u8], data: T) {
+    unsafe extern "C" fn free<T>(data: gpointer) {
+        drop(Box::from_raw(data as *mut T));
+    }
+
+    gobject_sys::g_object_set_data_full(
+        object.to_glib_none().0 as *mut _,
+        key.as_ptr() as *const c_char,
+        Box::into_raw(Box::new(data)) as gpointer,
+        Some(free::<T>),
+    );
+}
+
+unsafe fn get_data<'a, T>(object: &atk::Object, key: &[u8]) -> Option<&'a T> {
+    get_raw_data(object.to_glib_none().0, key)
+}
+
+unsafe fn get_raw_data<'a, T>(object: *mut atk_sys::AtkObject, key: &[u8]) -> Option<&'a T> {
+    let data = gobject_sys::g_object_get_data(object as *mut _, key.as_ptr() as *const c_char);
+
+    (data as *const T).as_ref()
+}
+
+// registers a type with the size of its parent, since none of them add any
+// fields of their own
+fn register_type(
+    name: &str,
+    parent: GType,
+    class_init: unsafe extern "C" fn(gpointer, gpointer),
+) -> GType {
+    let name = CString::new(name).expect("type name has a nul byte");
+
+    unsafe {
+        let existing = gobject_sys::g_type_from_name(name.as_ptr());
+        if existing != 0 {
+            return existing;
+        }
+
+        let mut query: gobject_sys::GTypeQuery = mem::zeroed();
+        gobject_sys::g_type_query(parent, &mut query);
+
+        gobject_sys::g_type_register_static_simple(
+            parent,
+            name.as_ptr(),
+            query.class_size,
+            Some(class_init),
+            query.instance_size,
+            None,
+            0,
+        )
+    }
+}
+
+fn page_area_get_type() -> GType {
+    unsafe extern "C" fn class_init(class: gpointer, _: gpointer) {
+        gtk_sys::gtk_widget_class_set_accessible_type(
+            class as *mut gtk_sys::GtkWidgetClass,
+            page_accessible_get_type(),
+        );
+    }
+
+    register_type(
+        "MoonrenderPageArea",
+        unsafe { gtk_sys::gtk_drawing_area_get_type() },
+        class_init,
+    )
+}
+
+static mut PAGE_PARENT_CLASS: *const atk_sys::AtkObjectClass = ptr::null();
+
+fn page_accessible_get_type() -> GType {
+    unsafe extern "C" fn class_init(class: gpointer, _: gpointer) {
+        PAGE_PARENT_CLASS = gobject_sys::g_type_class_peek_parent(class) as *const _;
+
+        let class = &mut *(class as *mut atk_sys::AtkObjectClass);
+        class.get_n_children = Some(page_get_n_children);
+        class.ref_child = Some(page_ref_child);
+        class.ref_state_set = Some(page_ref_state_set);
+    }
+
+    register_type(
+        "MoonrenderPageAccessible",
+        unsafe { gtk_sys::gtk_widget_accessible_get_type() },
+        class_init,
+    )
+}
+
+unsafe extern "C" fn page_get_n_children(page: *mut atk_sys::AtkObject) -> c_int {
+    get_raw_data::<Nodes>(page, NODES_KEY)
+        .map(|nodes| nodes.borrow().len() as c_int)
+        .unwrap_or(0)
+}
+
+unsafe extern "C" fn page_ref_child(
+    page: *mut atk_sys::AtkObject,
+    i: c_int,
+) -> *mut atk_sys::AtkObject {
+    get_raw_data::<Nodes>(page, NODES_KEY)
+        .and_then(|nodes| {
+            nodes
+                .borrow()
+                .get(i as usize)
+                .map(|node| node.to_glib_full())
+        })
+        .unwrap_or_else(ptr::null_mut)
+}
+
+// the page tells which line has the focus through active-descendant-changed
+unsafe extern "C" fn page_ref_state_set(
+    page: *mut atk_sys::AtkObject,
+) -> *mut atk_sys::AtkStateSet {
+    let set = match PAGE_PARENT_CLASS
+        .as_ref()
+        .and_then(|parent| parent.ref_state_set)
+    {
+        Some(ref_state_set) => ref_state_set(page),
+        None => atk_sys::atk_state_set_new(),
+    };
+
+    atk_sys::atk_state_set_add_state(set, atk_sys::ATK_STATE_MANAGES_DESCENDANTS);
+    set
+}
+
+fn line_accessible_get_type() -> GType {
+    unsafe extern "C" fn class_init(class: gpointer, _: gpointer) {
+        let class = &mut *(class as *mut atk_sys::AtkObjectClass);
+        class.get_index_in_parent = Some(line_get_index_in_parent);
+        class.ref_state_set = Some(line_ref_state_set);
+        class.get_attributes = Some(line_get_attributes);
+    }
+
+    register_type(
+        "MoonrenderLineAccessible",
+        unsafe { atk_sys::atk_object_get_type() },
+        class_init,
+    )
+}
+
+unsafe extern "C" fn line_get_index_in_parent(line: *mut atk_sys::AtkObject) -> c_int {
+    get_raw_data::<Node>(line, NODE_KEY)
+        .map(|node| node.index as c_int)
+        .unwrap_or(-1)
+}
+
+unsafe extern "C" fn line_ref_state_set(
+    line: *mut atk_sys::AtkObject,
+) -> *mut atk_sys::AtkStateSet {
+    let set = atk_sys::atk_state_set_new();
+
+    for state in &[
+        atk_sys::ATK_STATE_ENABLED,
+        atk_sys::ATK_STATE_SENSITIVE,
+        atk_sys::ATK_STATE_VISIBLE,
+        atk_sys::ATK_STATE_SHOWING,
+    ] {
+        atk_sys::atk_state_set_add_state(set, *state);
+    }
+
+    if let Some(node) = get_raw_data::<Node>(line, NODE_KEY) {
+        if node.focusable {
+            atk_sys::atk_state_set_add_state(set, atk_sys::ATK_STATE_FOCUSABLE);
+        }
+
+        if node.focused.get() {
+            atk_sys::atk_state_set_add_state(set, atk_sys::ATK_STATE_FOCUSED);
+        }
+    }
+
+    set
+}
+
+// the heading level and link target, the same attributes browsers use
+unsafe extern "C" fn line_get_attributes(
+    line: *mut atk_sys::AtkObject,
+) -> *mut atk_sys::AtkAttributeSet {
+    let mut set: *mut atk_sys::AtkAttributeSet = ptr::null_mut();
+
+    if let Some(node) = get_raw_data::<Node>(line, NODE_KEY) {
+        if let Some(level) = node.level {
+            set = add_attribute(set, "level", &level.to_string());
+        }
+
+        if let Some(target) = &node.target {
+            set = add_attribute(set, "href", target);
+        }
+    }
+
+    set
+}
+
+// the set and the attributes in it are freed by whoever asked for them
+unsafe fn add_attribute(
+    set: *mut atk_sys::AtkAttributeSet,
+    name: &str,
+    value: &str,
+) -> *mut atk_sys::AtkAttributeSet {
+    let attribute =
+        glib_sys::g_malloc0(mem::size_of::<atk_sys::AtkAttribute>()) as *mut atk_sys::AtkAttribute;
+
+    (*attribute).name = name.to_glib_full();
+    (*attribute).value = value.to_glib_full();
+
+    glib_sys::g_slist_prepend(set, attribute as gpointer)
+}
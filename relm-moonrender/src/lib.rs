@@ -1,28 +1,101 @@
+#[macro_use]
+extern crate glib;
+
+mod accessible;
+mod print;
+mod save;
+
 use anyhow::Context;
+use gdk::enums::key;
+use gdk::WindowExt as _;
 use gtk::prelude::*;
 use relm::{Channel, DrawHandler, Relm, Widget};
 use relm_derive::{widget, Msg};
-use std::sync::mpsc;
+use save::{Body, Progress, Queue};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
 use url::Url;
 
 pub use moonrender;
-use moonrender::{Msg as RendererMsg, Renderer};
+use moonrender::{DecodedImage, FindOptions, Msg as RendererMsg, Renderer};
+pub use save::{file_name, open_name};
 
 const ERROR_PAGE: &str = include_str!("error.gemini");
+const UNSUPPORTED_PAGE: &str = include_str!("unsupported.gemini");
 const SUPPORTED_PROTOCOLS: &[&str] = &["gemini"];
 
+// pixels to scroll horizontally per scroll wheel step
+const SCROLL_STEP: f64 = 40.0;
+
+// time spent laying out the page per main loop iteration
+const LAYOUT_TIME: Duration = Duration::from_millis(8);
+
 #[derive(Msg)]
 pub enum Msg {
     UnsupportedRedirect(String),
     Goto(String),
+    // open the url in a new window, listened by parent
+    OpenNew(String),
     Error(anyhow::Error),
     Done,
 
     UpdateDrawBuffer,
+    // lay out the next slice of a long page
+    Layout,
+    // show the next frame of animated images, if still on the same page
+    Animate(u64),
+    // (page, line, body) of something a line asked for, like an inline image
+    LineContent(u64, usize, anyhow::Result<DecodedImage>),
 
     MousePress(gdk::EventButton),
     MouseRelease(gdk::EventButton),
     MouseMove(gdk::EventMotion),
+    Scroll(gdk::EventScroll),
+    KeyPress(gdk::EventKey),
+
+    Copy,
+    // images on the page, see Renderer::set_zoom
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    // print the page, laid out with the given theme
+    Print(moonrender::Theme),
+    // charset for pages that don't name one
+    SetFallbackEncoding(String),
+
+    // the page's mime type can't be shown, with a file name for it. its
+    // body is kept until SaveBody or DiscardBody
+    Unsupported(String, String),
+    // bytes of the unsupported page received so far
+    BodyProgress(usize),
+    // save the unsupported page to a file, and whether to open it after
+    SaveBody(PathBuf, bool),
+    DiscardBody,
+    // the unsupported page is completely saved to the file
+    BodySaved(PathBuf, bool),
+
+    // save the url into the file without showing it. the number tells the
+    // downloads apart in the messages below
+    Download(u64, String, PathBuf),
+    CancelDownload(u64),
+    DownloadCreated(u64, PathBuf),
+    DownloadProgress(u64, usize),
+    DownloadDone(u64),
+    DownloadFailed(u64, String),
+
+    Find(String, FindOptions),
+    FindNext,
+    FindPrevious,
+    FindClear,
+    // (index of the current match, amount of matches)
+    FindResult(Option<usize>, usize),
 
     Back,
     Forward,
@@ -43,6 +116,29 @@ pub struct Model {
     renderer: Renderer,
 
     redirect_counter: u8,
+
+    // whether a Layout message is already on its way
+    layout_pending: bool,
+    // the page an Animate message is on its way for
+    animation_pending: Option<u64>,
+    // bumped on every page load, so animations and line contents of old
+    // pages are ignored
+    page: u64,
+
+    _content_channel: Channel<(u64, usize, anyhow::Result<DecodedImage>)>,
+    content_sender: relm::Sender<(u64, usize, anyhow::Result<DecodedImage>)>,
+    // (page, line, url) of what lines asked for, see load_requests
+    loads: Queue<(u64, usize, String)>,
+
+    // the current page, if it can't be shown
+    body: Option<Body>,
+
+    downloads: HashMap<u64, (Arc<AtomicBool>, Channel<Progress>)>,
+
+    // whether the link picked with hints opens in a new window
+    hint_new_window: bool,
+
+    accessible: accessible::Tree,
 }
 
 #[widget]
@@ -53,6 +149,11 @@ impl Widget for Moonrender {
         let (channel, sender) = Channel::new(move |msg| stream.emit(Msg::ConnectionMessage(msg)));
         let (send, recv) = mpsc::channel::<String>();
 
+        let stream = relm.stream().clone();
+        let (content_channel, content_sender) = Channel::new(move |(page, line, content)| {
+            stream.emit(Msg::LineContent(page, line, content))
+        });
+
         std::thread::spawn(move || {
             let recv = recv;
             let sender = sender;
@@ -82,11 +183,27 @@ impl Widget for Moonrender {
             renderer: Renderer::new(theme),
 
             redirect_counter: 0,
+
+            layout_pending: false,
+            animation_pending: None,
+            page: 0,
+
+            _content_channel: content_channel,
+            content_sender,
+            loads: Queue::new(save::MAX_LOADS),
+
+            body: None,
+
+            downloads: HashMap::new(),
+
+            hint_new_window: false,
+
+            accessible: accessible::Tree::default(),
         }
     }
 
     fn init_view(&mut self) {
-        self.model.draw.init(&self.content);
+        self.model.draw.init(self.content.upcast_ref());
 
         self.content.add_events(gdk::EventMask::ALL_EVENTS_MASK); // TODO: maybe make this more granular
     }
@@ -107,7 +224,7 @@ impl Widget for Moonrender {
                 orientation: gtk::Orientation::Horizontal,
 
                 #[name="content"]
-                gtk::DrawingArea {
+                accessible::PageArea {
                     child: {
                         expand: true,
                     },
@@ -119,6 +236,8 @@ impl Widget for Moonrender {
                     motion_notify_event(_, e) => (Msg::MouseMove(e.clone()), Inhibit(false)),
                     button_press_event(_, e) => (Msg::MousePress(e.clone()), Inhibit(false)),
                     button_release_event(_, e) => (Msg::MouseRelease(e.clone()), Inhibit(false)),
+                    scroll_event(_, e) => (Msg::Scroll(e.clone()), Inhibit(is_zoom_scroll(e))),
+                    key_press_event(_, e) => (Msg::KeyPress(e.clone()), Inhibit(is_page_key(e))),
                 },
             },
         }
@@ -142,29 +261,128 @@ impl Moonrender {
                 let w = size.width;
 
                 self.content.set_size_request(w, h);
+
+                // resizing starts the layout over
+                if !self.model.renderer.is_laid_out() {
+                    self.schedule_layout();
+                }
+
+                self.load_requests();
+            }
+
+            Msg::Layout => {
+                self.model.layout_pending = false;
+
+                self.update_size();
+                self.content.queue_draw();
             }
 
             Msg::MouseMove(e) => {
                 let coords = e.get_coords().context("move coords empty")?;
+
+                let hit = self.model.renderer.hit_test(coords);
+                self.set_link_cursor(hit.map_or(false, |hit| hit.link.is_some()));
+
                 let message = self.model.renderer.on_mouse_move(coords);
 
-                if let Some(msg) = message {
-                    if let RendererMsg::Tooltip(tooltip) = msg {
+                match message {
+                    Some(RendererMsg::Tooltip(tooltip)) => {
                         self.model.relm.stream().emit(Msg::ShowTooltip(tooltip));
                         return Ok(());
                     }
+                    Some(msg) => self.handle_renderer_msg(msg),
+                    None => {}
                 }
 
                 self.model.relm.stream().emit(Msg::HideTooltip);
             }
 
+            Msg::LineContent(page, line, content) => {
+                self.model.loads.done();
+                self.start_loads();
+
+                if page == self.model.page {
+                    self.model.renderer.set_content(line, content);
+
+                    self.update_size();
+                    self.content.queue_draw();
+
+                    // the image might be animated
+                    self.animate();
+                }
+            }
+
+            Msg::Animate(page) => {
+                if self.model.animation_pending == Some(page) {
+                    self.model.animation_pending = None;
+                }
+
+                if page == self.model.page {
+                    self.animate();
+                }
+            }
+
+            Msg::Scroll(e) => {
+                let coords = e.get_coords().context("scroll coords empty")?;
+
+                if is_zoom_scroll(&e) {
+                    let dy = match e.get_direction() {
+                        gdk::ScrollDirection::Up => -1.0,
+                        gdk::ScrollDirection::Down => 1.0,
+                        gdk::ScrollDirection::Smooth => e.get_delta().1,
+                        _ => 0.0,
+                    };
+
+                    if dy < 0.0 {
+                        self.model.relm.stream().emit(Msg::ZoomIn);
+                    } else if dy > 0.0 {
+                        self.model.relm.stream().emit(Msg::ZoomOut);
+                    }
+
+                    return Ok(());
+                }
+
+                let is_shift = e.get_state().contains(gdk::ModifierType::SHIFT_MASK);
+
+                let dx = match e.get_direction() {
+                    gdk::ScrollDirection::Left => -1.0,
+                    gdk::ScrollDirection::Right => 1.0,
+                    gdk::ScrollDirection::Up if is_shift => -1.0,
+                    gdk::ScrollDirection::Down if is_shift => 1.0,
+                    gdk::ScrollDirection::Smooth => {
+                        let (dx, dy) = e.get_delta();
+                        if is_shift && dx == 0.0 {
+                            dy
+                        } else {
+                            dx
+                        }
+                    }
+                    _ => 0.0,
+                };
+
+                if dx != 0.0 && self.model.renderer.on_scroll(coords, dx * SCROLL_STEP) {
+                    self.content.queue_draw();
+                }
+            }
+
             Msg::MousePress(e) => match e.get_button() {
                 8 => self.model.relm.stream().emit(Msg::Back),
                 9 => self.model.relm.stream().emit(Msg::Forward),
-                _ => {
-                    let coords = e.get_coords().context("click release coords empty")?;
-                    let _message = self.model.renderer.on_mouse_press(coords);
+                1 => {
+                    self.content.grab_focus();
+
+                    let coords = e.get_coords().context("click press coords empty")?;
+                    let clicks = match e.get_event_type() {
+                        gdk::EventType::DoubleButtonPress => 2,
+                        gdk::EventType::TripleButtonPress => 3,
+                        _ => 1,
+                    };
+
+                    if let Some(msg) = self.model.renderer.on_mouse_press(coords, clicks) {
+                        self.handle_renderer_msg(msg);
+                    }
                 }
+                _ => {}
             },
 
             Msg::MouseRelease(e) => {
@@ -172,12 +390,160 @@ impl Moonrender {
                 let message = self.model.renderer.on_mouse_release(coords);
 
                 if let Some(msg) = message {
-                    if let RendererMsg::Goto(url) = msg {
-                        self.model.relm.stream().emit(Msg::Goto(url));
+                    self.handle_renderer_msg(msg);
+                }
+            }
+
+            Msg::KeyPress(e) => {
+                let is_ctrl = e.get_state().contains(gdk::ModifierType::CONTROL_MASK);
+                let is_shift = e.get_state().contains(gdk::ModifierType::SHIFT_MASK);
+
+                if self.model.renderer.is_showing_hints() {
+                    match e.get_keyval() {
+                        key::Escape => self.model.renderer.hide_hints(),
+                        key::BackSpace => self.model.renderer.hint_backspace(),
+                        keyval => {
+                            if let Some(chr) =
+                                gdk::keyval_to_unicode(keyval).filter(char::is_ascii_alphabetic)
+                            {
+                                self.model.hint_new_window |= is_shift;
+
+                                match self.model.renderer.hint_input(chr) {
+                                    Some(RendererMsg::Goto(url)) if self.model.hint_new_window => {
+                                        self.model.relm.stream().emit(Msg::OpenNew(url))
+                                    }
+                                    Some(msg) => self.handle_renderer_msg(msg),
+                                    None => {}
+                                }
+                            }
+                        }
+                    }
+
+                    self.content.queue_draw();
+                    return Ok(());
+                }
+
+                let adjustment = match self.window.get_vadjustment() {
+                    Some(adjustment) => adjustment,
+                    None => return Ok(()),
+                };
+
+                let step = adjustment.get_step_increment();
+                let page = adjustment.get_page_increment();
+
+                match e.get_keyval() {
+                    key::c | key::C if is_ctrl => self.model.relm.stream().emit(Msg::Copy),
+                    key::a | key::A if is_ctrl => {
+                        self.model.renderer.select_all();
+                        self.content.queue_draw();
+                    }
+
+                    key::plus | key::equal | key::KP_Add if is_ctrl => {
+                        self.model.relm.stream().emit(Msg::ZoomIn)
+                    }
+                    key::minus | key::KP_Subtract if is_ctrl => {
+                        self.model.relm.stream().emit(Msg::ZoomOut)
+                    }
+                    key::_0 | key::KP_0 if is_ctrl => self.model.relm.stream().emit(Msg::ZoomReset),
+
+                    _ if is_ctrl => {}
+
+                    key::f | key::F => {
+                        self.model.hint_new_window = is_shift;
+
+                        if self.model.renderer.show_hints() {
+                            self.content.queue_draw();
+                        }
+                    }
+
+                    key::Tab => {
+                        let rect = self.model.renderer.focus_next();
+                        self.show_focus(rect);
+
+                        // past the last link, focus the widget after the page
+                        if rect.is_none() {
+                            self.content.emit_move_focus(gtk::DirectionType::TabForward);
+                        }
+                    }
+                    key::ISO_Left_Tab => {
+                        let rect = self.model.renderer.focus_previous();
+                        self.show_focus(rect);
+
+                        if rect.is_none() {
+                            self.content
+                                .emit_move_focus(gtk::DirectionType::TabBackward);
+                        }
+                    }
+                    key::Return | key::KP_Enter => {
+                        if let Some(msg) = self.model.renderer.activate_focus() {
+                            self.handle_renderer_msg(msg);
+                        }
+                    }
+
+                    key::Up => adjustment.set_value(adjustment.get_value() - step),
+                    key::Down => adjustment.set_value(adjustment.get_value() + step),
+                    key::space if is_shift => adjustment.set_value(adjustment.get_value() - page),
+                    key::Page_Up => adjustment.set_value(adjustment.get_value() - page),
+                    key::space | key::Page_Down => {
+                        adjustment.set_value(adjustment.get_value() + page)
                     }
+                    key::Home => adjustment.set_value(adjustment.get_lower()),
+                    key::End => adjustment.set_value(adjustment.get_upper()),
+                    _ => {}
                 }
             }
 
+            Msg::Copy => {
+                if let Some(text) = self.model.renderer.get_selection() {
+                    gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+                }
+            }
+
+            Msg::ZoomIn => {
+                self.model.renderer.zoom_in();
+                self.zoomed();
+            }
+            Msg::ZoomOut => {
+                self.model.renderer.zoom_out();
+                self.zoomed();
+            }
+            Msg::ZoomReset => {
+                self.model.renderer.set_zoom(1.0);
+                self.zoomed();
+            }
+
+            Msg::Print(theme) => {
+                let page = self.model.renderer.with_theme(theme)?;
+                let parent = self
+                    .content
+                    .get_toplevel()
+                    .and_then(|w| w.downcast::<gtk::Window>().ok());
+
+                print::print(page, parent.as_ref())?;
+            }
+
+            Msg::Find(query, options) => {
+                self.model.renderer.find(&query, options);
+
+                let pos = self.model.renderer.find_next();
+                self.show_match(pos);
+            }
+
+            Msg::FindNext => {
+                let pos = self.model.renderer.find_next();
+                self.show_match(pos);
+            }
+
+            Msg::FindPrevious => {
+                let pos = self.model.renderer.find_previous();
+                self.show_match(pos);
+            }
+
+            Msg::FindClear => {
+                self.model.renderer.clear_find();
+                self.content.queue_draw();
+            }
+
             Msg::Goto(url) => {
                 let url = Url::parse(&url).context("Cannot parse URL")?;
 
@@ -188,6 +554,13 @@ impl Moonrender {
                         .emit(Msg::UnsupportedRedirect(url.to_string()));
                 } else {
                     self.model.renderer.reset();
+                    self.model.body = None;
+                    self.model.page += 1;
+                    self.model.loads.clear();
+
+                    if let Some(page) = self.content.get_accessible() {
+                        self.model.accessible.clear(&page, url.as_str());
+                    }
 
                     self.model
                         .request
@@ -202,13 +575,47 @@ impl Moonrender {
             }
 
             Msg::ConnectionMessage(gemini::Message::Chunk(chunk)) => {
-                self.model.renderer.new_page_chunk(&chunk)?;
+                if let Some(body) = &mut self.model.body {
+                    body.push(&chunk)?;
+
+                    let received = body.received;
+                    self.model.relm.stream().emit(Msg::BodyProgress(received));
+
+                    return Ok(());
+                }
+
+                self.model.renderer.new_page_bytes(&chunk)?;
+                self.update_size();
+                self.update_accessible();
             }
 
             Msg::ConnectionMessage(gemini::Message::MIME(mime)) => {
+                let parsed = mime.parse().context("Cannot parse response mimetype")?;
+
+                if self.model.renderer.is_supported(&parsed) {
+                    self.model.renderer.set_mime(parsed);
+                    return Ok(());
+                }
+
+                // stream the body aside, and explain what happened in its place
+                let name = self
+                    .model
+                    .renderer
+                    .data
+                    .url
+                    .as_ref()
+                    .map(save::file_name)
+                    .unwrap_or_else(|| "download".to_owned());
+
+                self.model.body = Some(Body::default());
+
+                self.model.renderer.set_mime("text/gemini".parse().unwrap());
                 self.model
                     .renderer
-                    .set_mime(mime.parse().context("Cannot parse response mimetype")?);
+                    .new_page_chunk(&UNSUPPORTED_PAGE.replace("{mime}", &mime))?;
+                self.update_size();
+
+                self.model.relm.stream().emit(Msg::Unsupported(mime, name));
             }
 
             Msg::ConnectionMessage(gemini::Message::Redirect(url)) => {
@@ -225,6 +632,15 @@ impl Moonrender {
             }
 
             Msg::ConnectionMessage(gemini::Message::Done) => {
+                if let Some(body) = &mut self.model.body {
+                    if body.finish()? {
+                        self.emit_body_saved();
+                    }
+                }
+
+                self.model.renderer.finish_page()?;
+                self.update_size();
+
                 self.model.relm.stream().emit(Msg::Done);
             }
 
@@ -264,10 +680,72 @@ impl Moonrender {
                 self.model.relm.stream().emit(Msg::Done);
             }
 
+            Msg::SetFallbackEncoding(label) => {
+                self.model.renderer.set_fallback_encoding(&label)?;
+            }
+
+            Msg::SaveBody(path, open) => {
+                if let Some(body) = &mut self.model.body {
+                    if body.save(path, open)? {
+                        self.emit_body_saved();
+                    }
+                }
+            }
+
+            Msg::DiscardBody => {
+                if let Some(body) = &mut self.model.body {
+                    body.discard();
+                }
+            }
+
+            Msg::Download(id, url, path) => {
+                let stream = self.model.relm.stream().clone();
+                let (channel, sender) = Channel::new(move |progress| {
+                    stream.emit(match progress {
+                        Progress::Created(path) => Msg::DownloadCreated(id, path),
+                        Progress::Received(received) => Msg::DownloadProgress(id, received),
+                        Progress::Done => Msg::DownloadDone(id),
+                        Progress::Failed(e) => Msg::DownloadFailed(id, e),
+                    })
+                });
+
+                let cancel = Arc::new(AtomicBool::new(false));
+                save::download(url, path, cancel.clone(), move |progress| {
+                    // nobody listens anymore once it's cancelled
+                    let _ = sender.send(progress);
+                });
+
+                self.model.downloads.insert(id, (cancel, channel));
+            }
+
+            Msg::CancelDownload(id) => {
+                if let Some((cancel, _)) = self.model.downloads.remove(&id) {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            }
+
+            Msg::DownloadDone(id) | Msg::DownloadFailed(id, _) => {
+                // also listened by parent
+                self.model.downloads.remove(&id);
+            }
+
+            Msg::DownloadCreated(_, _) => { /* listened by parent */ }
+            Msg::DownloadProgress(_, _) => { /* listened by parent */ }
+            Msg::Unsupported(_, _) => { /* listened by parent */ }
+            Msg::BodyProgress(_) => { /* listened by parent */ }
+            Msg::BodySaved(_, _) => { /* listened by parent */ }
+
             Msg::Back => { /* listened by parent */ }
             Msg::Forward => { /* listened by parent */ }
 
-            Msg::Done => { /* listened by parent */ }
+            Msg::Done => {
+                // also listened by parent
+                self.animate();
+
+                self.update_accessible();
+            }
+            Msg::OpenNew(_) => { /* listened by parent */ }
+            Msg::FindResult(_, _) => { /* listened by parent */ }
             Msg::UnsupportedRedirect(_) => { /* listened by parent */ }
 
             Msg::ShowTooltip(_) => { /* listened by parent */ }
@@ -276,4 +754,201 @@ impl Moonrender {
 
         Ok(())
     }
+
+    fn show_match(&mut self, pos: Option<(f64, f64)>) {
+        if let (Some((_, y)), Some(adjustment)) = (pos, self.window.get_vadjustment()) {
+            // leave some context above the match
+            adjustment.set_value(y - adjustment.get_page_size() / 3.0);
+        }
+
+        self.content.queue_draw();
+
+        let (current, count) = self.model.renderer.get_find_status();
+        self.model
+            .relm
+            .stream()
+            .emit(Msg::FindResult(current, count));
+    }
+
+    fn zoomed(&mut self) {
+        self.update_size();
+        self.content.queue_draw();
+    }
+
+    // the page height is known before it's drawn, so the scrollbar is right
+    // from the start. long pages are laid out a slice at a time, and the
+    // height is estimated until they're done
+    fn update_size(&mut self) {
+        let width = self.content.get_allocated_width() as f64;
+        let (height, done) = self.model.renderer.layout_step(width, LAYOUT_TIME);
+
+        let (size, _) = self.content.get_preferred_size();
+        self.content.set_size_request(size.width, height as i32);
+
+        if !done {
+            self.schedule_layout();
+        }
+    }
+
+    // the pointer turns into a hand over links
+    fn set_link_cursor(&self, over_link: bool) {
+        if let Some(window) = self.content.get_window() {
+            let cursor = if over_link {
+                gdk::Cursor::new_from_name(&window.get_display(), "pointer")
+            } else {
+                None
+            };
+
+            window.set_cursor(cursor.as_ref());
+        }
+    }
+
+    // tells screen readers about the lines the page got since the last call
+    fn update_accessible(&mut self) {
+        if let Some(page) = self.content.get_accessible() {
+            let lines = self
+                .model
+                .renderer
+                .get_accessible_lines(self.model.accessible.len());
+
+            self.model.accessible.extend(&page, lines);
+        }
+    }
+
+    fn emit_body_saved(&self) {
+        if let Some(Body {
+            path: Some(path),
+            open,
+            ..
+        }) = &self.model.body
+        {
+            self.model
+                .relm
+                .stream()
+                .emit(Msg::BodySaved(path.clone(), *open));
+        }
+    }
+
+    // queues what lines on screen asked for, a few are loaded at once
+    fn load_requests(&mut self) {
+        for (line, url) in self.model.renderer.take_requests() {
+            let page = self.model.page;
+
+            let supported = Url::parse(&url)
+                .map(|url| SUPPORTED_PROTOCOLS.contains(&url.scheme()))
+                .unwrap_or(false);
+
+            if !supported {
+                let e = anyhow::anyhow!("Unsupported protocol in {}", url);
+                self.model.renderer.set_content(line, Err(e));
+                continue;
+            }
+
+            self.model.loads.push((page, line, url));
+        }
+
+        self.start_loads();
+    }
+
+    fn start_loads(&mut self) {
+        while let Some((page, line, url)) = self.model.loads.start() {
+            let sender = self.model.content_sender.clone();
+            save::load_image(url, move |content| {
+                // nobody listens anymore once the window is closed
+                let _ = sender.send((page, line, content));
+            });
+        }
+    }
+
+    // draws the current frame of animated images, and asks for the next one
+    // unless that's already on its way
+    fn animate(&mut self) {
+        if let Some(next) = self.model.renderer.animate() {
+            let page = self.model.page;
+
+            if self.model.animation_pending != Some(page) {
+                self.model.animation_pending = Some(page);
+
+                let millis = next.as_millis().max(1) as u32;
+                relm::timeout(self.model.relm.stream(), millis, move || Msg::Animate(page));
+            }
+
+            self.content.queue_draw();
+        }
+    }
+
+    fn schedule_layout(&mut self) {
+        if !self.model.layout_pending {
+            self.model.layout_pending = true;
+            relm::timeout(self.model.relm.stream(), 1, || Msg::Layout);
+        }
+    }
+
+    // scrolls just enough to show the focused line, and shows where it leads
+    fn show_focus(&mut self, rect: Option<(f64, f64, f64, f64)>) {
+        if let (Some((_, y, _, h)), Some(adjustment)) = (rect, self.window.get_vadjustment()) {
+            let top = adjustment.get_value();
+            let page_size = adjustment.get_page_size();
+
+            if y < top {
+                adjustment.set_value(y);
+            } else if y + h > top + page_size {
+                adjustment.set_value(y + h - page_size);
+            }
+        }
+
+        self.content.queue_draw();
+
+        if let Some(page) = self.content.get_accessible() {
+            let focus = self.model.renderer.get_focus();
+            self.model.accessible.set_focus(&page, focus);
+        }
+
+        match self.model.renderer.get_focus_tooltip() {
+            Some(tooltip) => self.model.relm.stream().emit(Msg::ShowTooltip(tooltip)),
+            None => self.model.relm.stream().emit(Msg::HideTooltip),
+        }
+    }
+
+    fn handle_renderer_msg(&mut self, msg: RendererMsg) {
+        match msg {
+            RendererMsg::Goto(url) => self.model.relm.stream().emit(Msg::Goto(url)),
+            RendererMsg::Tooltip(tooltip) => {
+                self.model.relm.stream().emit(Msg::ShowTooltip(tooltip))
+            }
+            RendererMsg::Redraw => self.content.queue_draw(),
+            RendererMsg::Select(text) => {
+                // the primary selection is pasted with a middle click on X11
+                gtk::Clipboard::get(&gdk::SELECTION_PRIMARY).set_text(&text);
+            }
+        }
+    }
+}
+
+// keys the page handles itself, instead of letting gtk move the focus or
+// activate other widgets with them
+fn is_page_key(e: &gdk::EventKey) -> bool {
+    if e.get_state().contains(gdk::ModifierType::CONTROL_MASK) {
+        return false;
+    }
+
+    [
+        key::Tab,
+        key::ISO_Left_Tab,
+        key::Return,
+        key::KP_Enter,
+        key::Up,
+        key::Down,
+        key::space,
+        key::Page_Up,
+        key::Page_Down,
+        key::Home,
+        key::End,
+    ]
+    .contains(&e.get_keyval())
+}
+
+// ctrl and the scroll wheel zooms instead of scrolling
+fn is_zoom_scroll(e: &gdk::EventScroll) -> bool {
+    e.get_state().contains(gdk::ModifierType::CONTROL_MASK)
 }
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use gtk::prelude::*;
+use moonrender::Renderer;
+use std::{cell::RefCell, rc::Rc};
+
+// pango lays text out at 96 dpi, while the print context is in points
+const PIXELS_PER_POINT: f64 = 96.0 / 72.0;
+
+// shows the print dialog for the page, which should already use the print theme
+pub fn print(page: Renderer, parent: Option<&gtk::Window>) -> Result<()> {
+    let operation = gtk::PrintOperation::new();
+    operation.set_unit(gtk::Unit::Points);
+
+    if let Some(url) = &page.data.url {
+        operation.set_job_name(url.as_str());
+    }
+
+    let page = Rc::new(RefCell::new(page));
+    let ranges = Rc::new(RefCell::new(vec![]));
+    let error = Rc::new(RefCell::new(None));
+
+    {
+        let page = page.clone();
+        let ranges = ranges.clone();
+        let error = error.clone();
+
+        operation.connect_begin_print(move |operation, ctx| {
+            let (width, height) = page_size(ctx);
+
+            match page.borrow_mut().paginate(width, height) {
+                Ok(pages) => {
+                    operation.set_n_pages(pages.len() as i32);
+                    *ranges.borrow_mut() = pages;
+                }
+                Err(e) => {
+                    *error.borrow_mut() = Some(e);
+                    operation.cancel();
+                }
+            }
+        });
+    }
+
+    operation.connect_draw_page(move |_, ctx, page_nr| {
+        let (width, _) = page_size(ctx);
+        let range = ranges.borrow().get(page_nr as usize).copied();
+
+        if let (Some(cairo), Some(range)) = (ctx.get_cairo_context(), range) {
+            cairo.scale(1.0 / PIXELS_PER_POINT, 1.0 / PIXELS_PER_POINT);
+            page.borrow_mut().draw_print_page(&cairo, width, range);
+        }
+    });
+
+    operation
+        .run(gtk::PrintOperationAction::PrintDialog, parent)
+        .context("Cannot print page")?;
+
+    // paginate failed, and the operation was cancelled
+    if let Some(e) = error.borrow_mut().take() {
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+// printable area, in pixels
+fn page_size(ctx: &gtk::PrintContext) -> (f64, f64) {
+    (
+        ctx.get_width() * PIXELS_PER_POINT,
+        ctx.get_height() * PIXELS_PER_POINT,
+    )
+}
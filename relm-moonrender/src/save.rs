@@ -0,0 +1,440 @@
+use anyhow::{anyhow, Context, Result};
+use moonrender::DecodedImage;
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use url::Url;
+
+const MAX_REDIRECTS: u8 = 5;
+
+// types that are opened without asking when the user chose to, with the
+// extension their file gets. other types could run code in whatever opens them
+const SAFE_TO_OPEN: &[(&str, &str)] = &[
+    ("application/pdf", "pdf"),
+    ("audio/flac", "flac"),
+    ("audio/mpeg", "mp3"),
+    ("audio/ogg", "ogg"),
+    ("image/gif", "gif"),
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+    ("text/plain", "txt"),
+    ("video/mp4", "mp4"),
+    ("video/webm", "webm"),
+];
+
+// downloads report how far they are at most this often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+// things loaded into memory, like inline images, stop at this size
+pub const MAX_LOAD_SIZE: usize = 16 * 1024 * 1024;
+// and only this many are loaded at once
+pub const MAX_LOADS: usize = 4;
+
+pub enum Progress {
+    // the file was created, with another name when the one asked for is taken
+    Created(PathBuf),
+    // bytes written so far
+    Received(usize),
+    Done,
+    Failed(String),
+}
+
+// where the body goes
+enum Target {
+    // kept in memory until the user decides what to do with it
+    Pending(Vec<u8>),
+    File(File),
+    Discard,
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::Pending(vec![])
+    }
+}
+
+// the body of a page the renderer can't show
+#[derive(Default)]
+pub struct Body {
+    pub received: usize,
+
+    pub path: Option<PathBuf>,
+    // open the file with another application once it's complete
+    pub open: bool,
+
+    done: bool,
+    target: Target,
+}
+
+impl Body {
+    pub fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        self.received += chunk.len();
+
+        match &mut self.target {
+            Target::Pending(buffer) => buffer.extend_from_slice(chunk),
+            Target::File(file) => file.write_all(chunk).context("Cannot write to file")?,
+            Target::Discard => {}
+        }
+
+        Ok(())
+    }
+
+    // writes what was received so far to the file, and the rest as it comes.
+    // a file that exists is never replaced, see create_unique. returns
+    // whether the file is complete
+    pub fn save(&mut self, path: PathBuf, open: bool) -> Result<bool> {
+        let (mut file, path) = create_unique(&path)?;
+
+        if let Target::Pending(buffer) = &self.target {
+            file.write_all(buffer).context("Cannot write to file")?;
+        }
+
+        self.target = Target::File(file);
+        self.path = Some(path);
+        self.open = open;
+
+        self.complete()
+    }
+
+    pub fn discard(&mut self) {
+        self.target = Target::Discard;
+    }
+
+    // the whole body was received, returns whether it's saved to a file
+    pub fn finish(&mut self) -> Result<bool> {
+        self.done = true;
+        self.complete()
+    }
+
+    fn complete(&mut self) -> Result<bool> {
+        match &mut self.target {
+            Target::File(file) if self.done => {
+                file.flush().context("Cannot write to file")?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+// the last part of the url's path, or "download" for urls like
+// gemini://host/
+pub fn file_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download")
+        .to_owned()
+}
+
+// the name with the extension of its type, for types that are safe to open
+// without asking. the server's extension isn't kept, since it decides what
+// opens the file
+pub fn open_name(name: &str, mime: &str) -> Option<String> {
+    let (_, extension) = SAFE_TO_OPEN.iter().find(|(safe, _)| *safe == mime)?;
+
+    let stem = Path::new(name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "download".to_owned());
+
+    Some(format!("{}.{}", stem, extension))
+}
+
+// creates the file, or "name (1).ext", "name (2).ext" and so on next to it
+// when the name is taken
+pub fn create_unique(path: &Path) -> Result<(File, PathBuf)> {
+    for n in 0..1000 {
+        let path = numbered(path, n);
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(anyhow!(e).context(format!("Cannot create {}", path.to_string_lossy())))
+            }
+        }
+    }
+
+    Err(anyhow!("No free file name for {}", path.to_string_lossy()))
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    if n == 0 {
+        return path.to_path_buf();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{} ({}).{}", stem, n, extension.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+
+    path.with_file_name(name)
+}
+
+// streams the body at url straight into path, on its own thread so pages keep
+// loading meanwhile. a file that exists is never replaced, see create_unique.
+// nothing is reported after cancel is set, and the partial file is removed
+pub fn download(
+    url: String,
+    path: PathBuf,
+    cancel: Arc<AtomicBool>,
+    progress: impl Fn(Progress) + Send + 'static,
+) {
+    std::thread::spawn(move || {
+        let (mut file, path) = match create_unique(&path) {
+            Ok(created) => created,
+            Err(e) => {
+                if !cancel.load(Ordering::Relaxed) {
+                    progress(Progress::Failed(format!("{:#}", e)));
+                }
+                return;
+            }
+        };
+
+        progress(Progress::Created(path.clone()));
+
+        let result = fetch(&url, &mut file, &cancel, &progress);
+
+        if cancel.load(Ordering::Relaxed) {
+            let _ = fs::remove_file(&path);
+            return;
+        }
+
+        match result {
+            Ok(()) => progress(Progress::Done),
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                progress(Progress::Failed(format!("{:#}", e)));
+            }
+        }
+    });
+}
+
+// fetches and decodes an image shown inside a page on its own thread, so
+// neither blocks the main loop. bodies larger than MAX_LOAD_SIZE fail
+pub fn load_image(url: String, done: impl FnOnce(Result<DecodedImage>) + Send + 'static) {
+    std::thread::spawn(move || {
+        let mut body = Limited::new(MAX_LOAD_SIZE);
+        let result = fetch(&url, &mut body, &AtomicBool::new(false), &|_| {})
+            .and_then(|()| DecodedImage::decode(&body.bytes, moonrender::INLINE_LIMITS));
+
+        done(result);
+    });
+}
+
+// a body in memory that refuses to grow past its limit
+struct Limited {
+    bytes: Vec<u8>,
+    limit: usize,
+}
+
+impl Limited {
+    fn new(limit: usize) -> Self {
+        Self {
+            bytes: vec![],
+            limit,
+        }
+    }
+}
+
+impl Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.bytes.len() + buf.len() > self.limit {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("larger than {}", moonrender::format_size(self.limit)),
+            ));
+        }
+
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// loads waiting for their turn, so a page full of images doesn't start
+// them all at once
+pub struct Queue<T> {
+    waiting: VecDeque<T>,
+    running: usize,
+    max: usize,
+}
+
+impl<T> Queue<T> {
+    pub fn new(max: usize) -> Self {
+        Self {
+            waiting: VecDeque::new(),
+            running: 0,
+            max,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.waiting.push_back(item);
+    }
+
+    // the next item to start, if fewer than max are running. call done once
+    // it finished
+    pub fn start(&mut self) -> Option<T> {
+        if self.running >= self.max {
+            return None;
+        }
+
+        let item = self.waiting.pop_front()?;
+        self.running += 1;
+        Some(item)
+    }
+
+    pub fn done(&mut self) {
+        self.running = self.running.saturating_sub(1);
+    }
+
+    // drops the items that didn't start, the running ones still call done
+    pub fn clear(&mut self) {
+        self.waiting.clear();
+    }
+}
+
+fn fetch(
+    url: &str,
+    file: &mut impl Write,
+    cancel: &AtomicBool,
+    progress: &impl Fn(Progress),
+) -> Result<()> {
+    let mut url = Url::parse(url).context("Cannot parse URL")?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let mut received = 0;
+        let mut reported = Instant::now();
+
+        let mut redirect = None;
+        let mut result = Ok(());
+
+        gemini::get_while(url.as_str(), |msg| {
+            match msg {
+                gemini::Message::Chunk(chunk) => {
+                    if let Err(e) = file.write_all(&chunk) {
+                        result = Err(anyhow!(e).context("Cannot write to file"));
+                        return false;
+                    }
+
+                    received += chunk.len();
+                    if reported.elapsed() >= PROGRESS_INTERVAL {
+                        reported = Instant::now();
+                        progress(Progress::Received(received));
+                    }
+                }
+                gemini::Message::Redirect(to) => redirect = Some(to),
+                gemini::Message::ErrorResponse(code, meta) => {
+                    result = Err(anyhow!("Server responded with {}: {}", code, meta))
+                }
+                gemini::Message::Error(e) => result = Err(e),
+                gemini::Message::MIME(_) | gemini::Message::Done => {}
+            }
+
+            !cancel.load(Ordering::Relaxed)
+        })?;
+
+        result?;
+
+        match redirect {
+            Some(to) => url = url.join(&to).context("Cannot parse redirect URL")?,
+            None => {
+                progress(Progress::Received(received));
+                return file.flush().context("Cannot write to file");
+            }
+        }
+    }
+
+    Err(anyhow!("Redirect loop detected"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_to_open() {
+        let cases = [
+            ("photo.png", "image/png", Some("photo.png")),
+            ("photo.sh", "image/png", Some("photo.png")),
+            ("archive.tar.gz", "application/pdf", Some("archive.tar.pdf")),
+            ("notes", "text/plain", Some("notes.txt")),
+            ("run.desktop", "application/x-desktop", None),
+            ("page.html", "text/html", None),
+            ("script.sh", "application/x-sh", None),
+        ];
+
+        for (name, mime, expected) in cases.iter() {
+            assert_eq!(
+                open_name(name, mime).as_deref(),
+                *expected,
+                "{} {}",
+                name,
+                mime
+            );
+        }
+    }
+
+    #[test]
+    fn limited() {
+        let mut body = Limited::new(4);
+        assert!(body.write_all(b"ab").is_ok());
+        assert!(body.write_all(b"cd").is_ok());
+        assert!(body.write_all(b"e").is_err());
+        assert_eq!(body.bytes, b"abcd");
+    }
+
+    #[test]
+    fn queue() {
+        let mut queue = Queue::new(2);
+        for i in 0..4 {
+            queue.push(i);
+        }
+
+        assert_eq!(queue.start(), Some(0));
+        assert_eq!(queue.start(), Some(1));
+        assert_eq!(queue.start(), None);
+
+        queue.done();
+        assert_eq!(queue.start(), Some(2));
+        assert_eq!(queue.start(), None);
+
+        // running ones still finish after a clear
+        queue.clear();
+        queue.done();
+        queue.done();
+        assert_eq!(queue.start(), None);
+
+        queue.push(4);
+        assert_eq!(queue.start(), Some(4));
+    }
+
+    #[test]
+    fn numbered_names() {
+        let cases = [
+            ("dir/file.txt", 0, "dir/file.txt"),
+            ("dir/file.txt", 1, "dir/file (1).txt"),
+            ("dir/file.tar.gz", 2, "dir/file.tar (2).gz"),
+            ("dir/file", 3, "dir/file (3)"),
+        ];
+
+        for (path, n, expected) in cases.iter() {
+            assert_eq!(numbered(Path::new(path), *n), Path::new(expected));
+        }
+    }
+}
@@ -3,7 +3,7 @@ use crate::DIRS;
 use anyhow::{Context, Result};
 use relm_moonrender::moonrender::Theme;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{collections::HashMap, fs, path::PathBuf};
 
 lazy_static::lazy_static! {
     pub static ref CONFIG: Config = load_config().expect("Cannot load config");
@@ -13,36 +13,125 @@ lazy_static::lazy_static! {
 pub struct Config {
     pub homepage: String,
 
+    // charset for pages that don't name one
+    #[serde(default = "default_encoding")]
+    pub fallback_encoding: String,
+
     pub theme: Theme,
+
+    #[serde(default = "Theme::print")]
+    pub print_theme: Theme,
+
+    // what to do with pages that can't be shown, by mime type
+    #[serde(default)]
+    pub mime_actions: HashMap<String, MimeAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum MimeAction {
+    // into the downloads directory
+    Save,
+    // with the default application for the type
+    Open,
+}
+
+fn default_encoding() -> String {
+    "utf-8".to_owned()
 }
 
 fn default_config() -> Config {
     Config {
         homepage: "gemini://gemini.circumlunar.space".to_owned(),
 
+        fallback_encoding: default_encoding(),
+
         theme: Theme::default(),
+        print_theme: Theme::print(),
+
+        mime_actions: HashMap::new(),
     }
 }
 
+fn config_path() -> PathBuf {
+    DIRS.config_dir().join("config.toml")
+}
+
 fn load_config() -> Result<Config> {
-    let path = DIRS.config_dir().join("config.toml");
-    if path.exists() {
+    let path = config_path();
+    let mut cfg = if path.exists() {
         let content = fs::read_to_string(&path).context("Cannot read config")?;
 
         log::info!("Config read from {}", path.to_string_lossy());
-        toml::from_str(&content).context("Cannot parse TOML")
+        toml::from_str(&content).context("Cannot parse TOML")?
     } else {
         let cfg = default_config();
-        let out = toml::to_string_pretty(&cfg).context("Cannot serialize TOML")?;
+        write_config(&cfg).context("Cannot write default config")?;
 
-        let parent = path
-            .parent()
-            .context("Config path doesn't have a parent directory?")?;
+        log::info!("Written default config to {}", path.to_string_lossy());
+        cfg
+    };
 
-        fs::create_dir_all(parent).context("Cannot create config parent directories")?;
-        fs::write(&path, out).context("Cannot write default config")?;
+    // choices remembered from the dialog, the ones written in config.toml win
+    let mut mime_actions = load_remembered().unwrap_or_else(|e| {
+        log::error!("Cannot load remembered MIME actions: {:#}", e);
+        HashMap::new()
+    });
+    mime_actions.extend(cfg.mime_actions.drain());
+    cfg.mime_actions = mime_actions;
 
-        log::info!("Written default config to {}", path.to_string_lossy());
-        Ok(cfg)
+    Ok(cfg)
+}
+
+fn write_config(cfg: &Config) -> Result<()> {
+    let path = config_path();
+    let out = toml::to_string_pretty(cfg).context("Cannot serialize TOML")?;
+
+    let parent = path
+        .parent()
+        .context("Config path doesn't have a parent directory?")?;
+
+    fs::create_dir_all(parent).context("Cannot create config parent directories")?;
+    fs::write(&path, out).context("Cannot write config")
+}
+
+// kept apart from config.toml, so remembering a choice doesn't rewrite the
+// user's config and lose its comments
+#[derive(Serialize, Deserialize, Default)]
+struct Remembered {
+    mime_actions: HashMap<String, MimeAction>,
+}
+
+fn remembered_path() -> PathBuf {
+    DIRS.data_dir().join("mime_actions.toml")
+}
+
+fn load_remembered() -> Result<HashMap<String, MimeAction>> {
+    let path = remembered_path();
+    if !path.exists() {
+        return Ok(HashMap::new());
     }
+
+    let content = fs::read_to_string(&path).context("Cannot read remembered MIME actions")?;
+    let remembered: Remembered = toml::from_str(&content).context("Cannot parse TOML")?;
+
+    Ok(remembered.mime_actions)
+}
+
+// stores the choice for the next time, CONFIG stays the same
+pub fn remember_mime_action(mime: &str, action: MimeAction) -> Result<()> {
+    let mut remembered = Remembered {
+        mime_actions: load_remembered()?,
+    };
+    remembered.mime_actions.insert(mime.to_owned(), action);
+
+    let path = remembered_path();
+    let out = toml::to_string_pretty(&remembered).context("Cannot serialize TOML")?;
+
+    let parent = path
+        .parent()
+        .context("MIME actions path doesn't have a parent directory?")?;
+
+    fs::create_dir_all(parent).context("Cannot create data directories")?;
+    fs::write(&path, out).context("Cannot write remembered MIME actions")
 }
@@ -0,0 +1,50 @@
+use crate::DIRS;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+// a finished download, kept across restarts
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub url: String,
+    pub path: PathBuf,
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct History {
+    downloads: Vec<Entry>,
+}
+
+fn history_path() -> PathBuf {
+    DIRS.data_dir().join("downloads.toml")
+}
+
+pub fn load() -> Result<Vec<Entry>> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = fs::read_to_string(&path).context("Cannot read download history")?;
+    let history: History = toml::from_str(&content).context("Cannot parse TOML")?;
+
+    Ok(history.downloads)
+}
+
+pub fn save(downloads: &[Entry]) -> Result<()> {
+    let path = history_path();
+    let history = History {
+        downloads: downloads.to_vec(),
+    };
+
+    let out = toml::to_string_pretty(&history).context("Cannot serialize TOML")?;
+
+    let parent = path
+        .parent()
+        .context("Download history path doesn't have a parent directory?")?;
+
+    fs::create_dir_all(parent).context("Cannot create data directories")?;
+    fs::write(&path, out).context("Cannot write download history")
+}
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, bail, Context, Result};
+use relm_moonrender::moonrender::{ExportFormat, Renderer};
+use std::{path::Path, sync::mpsc};
+use url::Url;
+
+const EXPORT_WIDTH: f64 = 800.0;
+const MAX_REDIRECTS: u8 = 5;
+
+// fetches the url and renders it into path, for `moonlander --export`
+pub fn export(path: &Path, url: &str) -> Result<()> {
+    let format = ExportFormat::from_path(path)
+        .context("Cannot guess the export format, use a .png, .pdf or .svg file")?;
+
+    let mut renderer = Renderer::new(crate::CONFIG.theme.clone());
+    renderer.set_fallback_encoding(&crate::CONFIG.fallback_encoding)?;
+    let mut url = Url::parse(url).context("Cannot parse URL")?;
+    let mut redirects = 0;
+
+    'request: loop {
+        renderer.reset();
+        renderer.set_url(url.clone())?;
+
+        let (send, recv) = mpsc::channel();
+        gemini::get(url.as_str(), |msg| {
+            send.send(msg).expect("Cannot send message to export")
+        })?;
+
+        drop(send);
+
+        for msg in recv {
+            match msg {
+                gemini::Message::MIME(mime) => {
+                    renderer.set_mime(mime.parse().context("Cannot parse response mimetype")?)
+                }
+                gemini::Message::Chunk(chunk) => renderer.new_page_bytes(&chunk)?,
+                gemini::Message::Redirect(to) => {
+                    redirects += 1;
+                    if redirects > MAX_REDIRECTS {
+                        bail!("Redirect loop detected");
+                    }
+
+                    url = url.join(&to).context("Cannot parse redirect URL")?;
+                    continue 'request;
+                }
+                gemini::Message::ErrorResponse(code, msg) => {
+                    bail!("Server responded with {}: {}", code, msg)
+                }
+                gemini::Message::Error(e) => return Err(e),
+                gemini::Message::Done => {}
+            }
+        }
+
+        break;
+    }
+
+    renderer.finish_page()?;
+    renderer
+        .export(EXPORT_WIDTH, format, path)
+        .map_err(|e| anyhow!("Cannot export {}: {}", url, e))
+}
@@ -0,0 +1,344 @@
+use gtk::prelude::*;
+use gtk::Inhibit;
+use relm::{connect, Relm, Widget};
+use relm_derive::{widget, Msg};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::downloads::{self, Entry};
+use relm_moonrender::moonrender::format_size;
+
+#[derive(Msg)]
+pub enum Msg {
+    Show,
+    Hide,
+
+    // a download with this number started, from the url into the path
+    Start(u64, String, PathBuf),
+    // its file was created, with another name when the one in Start is taken
+    Created(u64, PathBuf),
+    Progress(u64, usize),
+    Done(u64),
+    Failed(u64, String),
+    // listened by parent, which stops the download
+    Cancel(u64),
+    Retry(u64),
+    Remove(u64),
+    // listened by parent, which starts the download again
+    Restart(String, PathBuf),
+
+    Open(PathBuf),
+    ShowInFolder(PathBuf),
+    Clear,
+}
+
+// a download that's still going
+struct Active {
+    url: String,
+    path: PathBuf,
+
+    started: Instant,
+    received: usize,
+
+    row: gtk::ListBoxRow,
+    name: gtk::Label,
+    status: gtk::Label,
+}
+
+// a download that failed, kept until it's retried or removed
+struct Failed {
+    url: String,
+    path: PathBuf,
+
+    row: gtk::ListBoxRow,
+}
+
+pub struct Model {
+    relm: Relm<Downloads>,
+
+    active: HashMap<u64, Active>,
+    failed: HashMap<u64, Failed>,
+
+    history: Vec<Entry>,
+    history_rows: Vec<gtk::ListBoxRow>,
+}
+
+#[widget]
+impl Widget for Downloads {
+    fn model(relm: &Relm<Self>, _: ()) -> Model {
+        let history = downloads::load().unwrap_or_else(|e| {
+            log::error!("Cannot load download history: {:#}", e);
+            vec![]
+        });
+
+        Model {
+            relm: relm.clone(),
+
+            active: HashMap::new(),
+            failed: HashMap::new(),
+
+            history,
+            history_rows: vec![],
+        }
+    }
+
+    fn init_view(&mut self) {
+        for entry in self.model.history.clone() {
+            let row = self.finished_row(&entry);
+            self.model.history_rows.push(row);
+        }
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::Show => self.window.present(),
+            Msg::Hide => self.window.hide(),
+
+            Msg::Start(id, url, path) => {
+                let cancel = gtk::Button::new_with_label("Cancel");
+                connect!(self.model.relm, cancel, connect_clicked(_), Msg::Cancel(id));
+
+                let (row, name, status) = self.add_row(&path, &[cancel]);
+                status.set_text("Starting…");
+
+                let active = Active {
+                    url,
+                    path,
+
+                    started: Instant::now(),
+                    received: 0,
+
+                    row,
+                    name,
+                    status,
+                };
+
+                self.model.active.insert(id, active);
+            }
+
+            Msg::Created(id, path) => {
+                if let Some(active) = self.model.active.get_mut(&id) {
+                    active.name.set_text(&file_name(&path));
+                    active.path = path;
+                }
+            }
+
+            Msg::Progress(id, received) => {
+                if let Some(active) = self.model.active.get_mut(&id) {
+                    active.received = received;
+
+                    let seconds = active.started.elapsed().as_secs_f64().max(0.001);
+                    let speed = (received as f64 / seconds) as usize;
+
+                    active.status.set_text(&format!(
+                        "{}, {}/s",
+                        format_size(received),
+                        format_size(speed)
+                    ));
+                }
+            }
+
+            Msg::Done(id) => {
+                if let Some(active) = self.model.active.remove(&id) {
+                    self.list.remove(&active.row);
+
+                    let entry = Entry {
+                        url: active.url,
+                        path: active.path,
+                        size: active.received,
+                    };
+
+                    let row = self.finished_row(&entry);
+                    self.model.history_rows.push(row);
+
+                    self.model.history.push(entry);
+                    self.save_history();
+                }
+            }
+
+            Msg::Failed(id, e) => {
+                if let Some(active) = self.model.active.remove(&id) {
+                    self.list.remove(&active.row);
+
+                    let row = self.failed_row(id, &active.path, &e);
+                    let failed = Failed {
+                        url: active.url,
+                        path: active.path,
+
+                        row,
+                    };
+
+                    self.model.failed.insert(id, failed);
+                }
+            }
+
+            Msg::Cancel(id) => {
+                if let Some(active) = self.model.active.remove(&id) {
+                    self.list.remove(&active.row);
+                }
+            }
+
+            Msg::Retry(id) => {
+                if let Some(failed) = self.model.failed.remove(&id) {
+                    self.list.remove(&failed.row);
+                    self.model
+                        .relm
+                        .stream()
+                        .emit(Msg::Restart(failed.url, failed.path));
+                }
+            }
+            Msg::Remove(id) => {
+                if let Some(failed) = self.model.failed.remove(&id) {
+                    self.list.remove(&failed.row);
+                }
+            }
+            Msg::Restart(_, _) => { /* listened by parent */ }
+
+            Msg::Open(path) => open(&path),
+            Msg::ShowInFolder(path) => {
+                if let Some(folder) = path.parent() {
+                    open(folder);
+                }
+            }
+
+            // only finished downloads, the files stay
+            Msg::Clear => {
+                for row in self.model.history_rows.drain(..) {
+                    self.list.remove(&row);
+                }
+
+                self.model.history.clear();
+                self.save_history();
+            }
+        }
+    }
+
+    view! {
+        #[name="window"]
+        gtk::Window {
+            title: "Downloads",
+            visible: false,
+            property_default_width: 480,
+            property_default_height: 360,
+
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+
+                gtk::ScrolledWindow {
+                    child: {
+                        expand: true
+                    },
+
+                    #[name="list"]
+                    gtk::ListBox {
+                        selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+
+                gtk::Button {
+                    label: "Clear Finished Downloads",
+                    clicked => Msg::Clear,
+                },
+            },
+
+            delete_event(_, _) => (Msg::Hide, Inhibit(true)),
+        }
+    }
+}
+
+impl Downloads {
+    // newest rows go on top
+    fn add_row(
+        &self,
+        path: &Path,
+        buttons: &[gtk::Button],
+    ) -> (gtk::ListBoxRow, gtk::Label, gtk::Label) {
+        let name = gtk::Label::new(Some(&file_name(path)));
+        name.set_xalign(0.0);
+
+        let status = gtk::Label::new(None);
+        status.set_xalign(0.0);
+        status.get_style_context().add_class("dim-label");
+
+        let labels = gtk::Box::new(gtk::Orientation::Vertical, 2);
+        labels.pack_start(&name, false, false, 0);
+        labels.pack_start(&status, false, false, 0);
+
+        let row_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        row_box.set_border_width(6);
+        row_box.pack_start(&labels, true, true, 0);
+
+        for button in buttons {
+            button.set_valign(gtk::Align::Center);
+            row_box.pack_end(button, false, false, 0);
+        }
+
+        let row = gtk::ListBoxRow::new();
+        row.add(&row_box);
+        row.show_all();
+
+        self.list.insert(&row, 0);
+
+        (row, name, status)
+    }
+
+    fn finished_row(&self, entry: &Entry) -> gtk::ListBoxRow {
+        let open = gtk::Button::new_with_label("Open");
+        let path = entry.path.clone();
+        connect!(
+            self.model.relm,
+            open,
+            connect_clicked(_),
+            Msg::Open(path.clone())
+        );
+
+        let show = gtk::Button::new_with_label("Show in Folder");
+        let path = entry.path.clone();
+        connect!(
+            self.model.relm,
+            show,
+            connect_clicked(_),
+            Msg::ShowInFolder(path.clone())
+        );
+
+        // pack_end puts the first button on the right
+        let (row, _, status) = self.add_row(&entry.path, &[open, show]);
+        status.set_text(&format!("{} from {}", format_size(entry.size), entry.url));
+
+        row
+    }
+
+    fn failed_row(&self, id: u64, path: &Path, e: &str) -> gtk::ListBoxRow {
+        let retry = gtk::Button::new_with_label("Retry");
+        connect!(self.model.relm, retry, connect_clicked(_), Msg::Retry(id));
+
+        let remove = gtk::Button::new_with_label("Remove");
+        connect!(self.model.relm, remove, connect_clicked(_), Msg::Remove(id));
+
+        let (row, _, status) = self.add_row(path, &[retry, remove]);
+        status.set_text(&format!("Failed: {}", e));
+
+        row
+    }
+
+    fn save_history(&self) {
+        if let Err(e) = downloads::save(&self.model.history) {
+            log::error!("Cannot save download history: {:#}", e);
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn open(path: &Path) {
+    if let Err(e) = webbrowser::open(&path.to_string_lossy()) {
+        log::error!("Cannot open {}: {}", path.to_string_lossy(), e);
+    }
+}
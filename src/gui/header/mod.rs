@@ -14,6 +14,12 @@ pub enum Msg {
     Back,
     Forward,
     Refresh,
+    Print,
+    SavePage,
+    ShowDownloads,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
 
     EnableBtnBack(bool),
     EnableBtnForward(bool),
@@ -46,6 +52,50 @@ impl Widget for Header {
     fn init_view(&mut self) {
         let addr = &self.model.address_bar;
         connect!(addr@ABMsg::Goto(ref url), self.model.relm, Msg::Goto(url.to_owned()));
+
+        let menu = gtk::Menu::new();
+
+        let save = gtk::MenuItem::new_with_mnemonic("_Save Page…");
+        connect!(self.model.relm, save, connect_activate(_), Msg::SavePage);
+        menu.append(&save);
+
+        let print = gtk::MenuItem::new_with_mnemonic("_Print…");
+        connect!(self.model.relm, print, connect_activate(_), Msg::Print);
+        menu.append(&print);
+
+        menu.append(&gtk::SeparatorMenuItem::new());
+
+        // zooming only scales images, text follows the theme
+        let zoom_in = gtk::MenuItem::new_with_mnemonic("Zoom _In Images");
+        connect!(self.model.relm, zoom_in, connect_activate(_), Msg::ZoomIn);
+        menu.append(&zoom_in);
+
+        let zoom_out = gtk::MenuItem::new_with_mnemonic("Zoom _Out Images");
+        connect!(self.model.relm, zoom_out, connect_activate(_), Msg::ZoomOut);
+        menu.append(&zoom_out);
+
+        let zoom_reset = gtk::MenuItem::new_with_mnemonic("_Fit Images");
+        connect!(
+            self.model.relm,
+            zoom_reset,
+            connect_activate(_),
+            Msg::ZoomReset
+        );
+        menu.append(&zoom_reset);
+
+        menu.append(&gtk::SeparatorMenuItem::new());
+
+        let downloads = gtk::MenuItem::new_with_mnemonic("_Downloads");
+        connect!(
+            self.model.relm,
+            downloads,
+            connect_activate(_),
+            Msg::ShowDownloads
+        );
+        menu.append(&downloads);
+
+        menu.show_all();
+        self.btn_menu.set_popup(Some(&menu));
     }
 
     fn update(&mut self, event: Msg) {
@@ -56,6 +106,12 @@ impl Widget for Header {
             Msg::Back => { /* listened from parent */ }
             Msg::Forward => { /* listened from parent */ }
             Msg::Refresh => { /* listened from parent */ }
+            Msg::Print => { /* listened from parent */ }
+            Msg::SavePage => { /* listened from parent */ }
+            Msg::ShowDownloads => { /* listened from parent */ }
+            Msg::ZoomIn => { /* listened from parent */ }
+            Msg::ZoomOut => { /* listened from parent */ }
+            Msg::ZoomReset => { /* listened from parent */ }
 
             Msg::EnableBtnBack(b) => self.model.has_history_back = b,
             Msg::EnableBtnForward(b) => self.model.has_history_forwards = b,
@@ -92,6 +148,14 @@ impl Widget for Header {
 
                 clicked => Msg::Refresh,
             },
+
+            #[name="btn_menu"]
+            gtk::MenuButton {
+                child: {
+                    pack_type: gtk::PackType::End,
+                },
+                image: Some(&gtk::Image::new_from_icon_name(Some("open-menu-symbolic"), gtk::IconSize::SmallToolbar)),
+            },
         },
     }
 }
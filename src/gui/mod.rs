@@ -1,13 +1,22 @@
+mod downloads;
 mod header;
 
+use gdk::enums::key;
 use gtk::prelude::*;
 use gtk::Inhibit;
 use gtk::WidgetExt;
 use relm::{connect, init, Component, Relm, Widget};
 use relm_derive::{widget, Msg};
+use std::{collections::HashMap, path::PathBuf};
 
+use crate::config::{self, MimeAction};
+
+use downloads::{Downloads, Msg as DownloadsMsg};
 use header::{Header, Msg as HeaderMsg};
-use relm_moonrender::{Moonrender, Msg as MoonrenderMsg};
+use relm_moonrender::{
+    moonrender::{format_size, FindOptions},
+    Moonrender, Msg as MoonrenderMsg,
+};
 
 #[derive(Msg)]
 pub enum Msg {
@@ -18,40 +27,79 @@ pub enum Msg {
 
     Redirect(String),
     UnsupportedRedirect(String),
+    OpenNewWindow(String),
+
+    // pages that can't be shown: mime type and file name, then what the user
+    // picked and whether to remember it
+    Unsupported(String, String),
+    UnsupportedChoice(String, String, Option<MimeAction>, bool),
+    BodyProgress(usize),
+    BodySaved(PathBuf, bool),
 
     Back,
     Forward,
     Refresh,
+    Print,
+    SavePage,
+    Download(String, PathBuf),
+    ShowDownloads,
 
     ShowTooltip(String),
     HideTooltip,
+
+    KeyPress(gdk::EventKey),
+
+    OpenFind,
+    Find,
+    FindNext,
+    FindPrevious,
+    FindModeChanged,
+    FindResult(Option<usize>, usize),
 }
 
 pub struct Model {
     relm: Relm<Win>,
     header: Component<Header>,
+    downloads: Component<Downloads>,
 
     status_ctx_goto: u32,
     status_ctx_tooltip: u32,
+    status_ctx_download: u32,
 
     history: Vec<String>,
     forward_history: Vec<String>,
+
+    find_status: String,
+
+    mime_actions: HashMap<String, MimeAction>,
+
+    // number of the next download, to tell them apart
+    next_download: u64,
 }
 
 #[widget]
 impl Widget for Win {
     fn model(relm: &Relm<Self>, _: ()) -> Model {
         let header = init::<Header>(()).expect("Header cannot be initialized");
+        let downloads = init::<Downloads>(()).expect("Downloads cannot be initialized");
 
         Model {
             header,
+            downloads,
             relm: relm.clone(),
 
             status_ctx_goto: 0,
             status_ctx_tooltip: 0,
+            status_ctx_download: 0,
 
             history: vec![],
             forward_history: vec![],
+
+            find_status: String::new(),
+
+            mime_actions: crate::CONFIG.mime_actions.clone(),
+
+            next_download: 0,
         }
     }
 
@@ -61,14 +109,23 @@ impl Widget for Win {
 
         self.model.status_ctx_goto = self.status.get_context_id("Navigation");
         self.model.status_ctx_tooltip = self.status.get_context_id("Tooltip");
+        self.model.status_ctx_download = self.status.get_context_id("Download");
 
         self.status.hide();
 
+        self.search_bar.connect_entry(&self.search_entry);
+
         connect!(header@HeaderMsg::Goto(ref url), self.model.relm, Msg::Goto(url.to_owned()));
 
         connect!(header@HeaderMsg::Back, self.model.relm, Msg::Back);
         connect!(header@HeaderMsg::Forward, self.model.relm, Msg::Forward);
         connect!(header@HeaderMsg::Refresh, self.model.relm, Msg::Refresh);
+        connect!(header@HeaderMsg::Print, self.model.relm, Msg::Print);
+        connect!(header@HeaderMsg::SavePage, self.model.relm, Msg::SavePage);
+        connect!(header@HeaderMsg::ShowDownloads, self.model.relm, Msg::ShowDownloads);
+        connect!(header@HeaderMsg::ZoomIn, content, MoonrenderMsg::ZoomIn);
+        connect!(header@HeaderMsg::ZoomOut, content, MoonrenderMsg::ZoomOut);
+        connect!(header@HeaderMsg::ZoomReset, content, MoonrenderMsg::ZoomReset);
 
         connect!(content@MoonrenderMsg::Back, self.model.relm, Msg::Back);
         connect!(content@MoonrenderMsg::Forward, self.model.relm, Msg::Forward);
@@ -78,10 +135,33 @@ impl Widget for Win {
 
         connect!(content@MoonrenderMsg::Done, self.model.relm, Msg::GotoDone);
 
+        connect!(content@MoonrenderMsg::FindResult(ref current, ref count), self.model.relm, Msg::FindResult(*current, *count));
+
         connect!(content@MoonrenderMsg::Goto(ref url), self.model.relm, Msg::Redirect(url.to_owned()));
         connect!(content@MoonrenderMsg::UnsupportedRedirect(ref url), self.model.relm, Msg::UnsupportedRedirect(url.clone()));
+        connect!(content@MoonrenderMsg::OpenNew(ref url), self.model.relm, Msg::OpenNewWindow(url.clone()));
+
+        connect!(content@MoonrenderMsg::Unsupported(ref mime, ref name), self.model.relm, Msg::Unsupported(mime.clone(), name.clone()));
+        connect!(content@MoonrenderMsg::BodyProgress(ref received), self.model.relm, Msg::BodyProgress(*received));
+        connect!(content@MoonrenderMsg::BodySaved(ref path, ref open), self.model.relm, Msg::BodySaved(path.clone(), *open));
+
+        let downloads = &self.model.downloads;
+        downloads.widget().set_transient_for(Some(&self.window));
 
-        let url = crate::CONFIG.homepage.clone();
+        connect!(content@MoonrenderMsg::DownloadCreated(ref id, ref path), downloads, DownloadsMsg::Created(*id, path.clone()));
+        connect!(content@MoonrenderMsg::DownloadProgress(ref id, ref received), downloads, DownloadsMsg::Progress(*id, *received));
+        connect!(content@MoonrenderMsg::DownloadDone(ref id), downloads, DownloadsMsg::Done(*id));
+        connect!(content@MoonrenderMsg::DownloadFailed(ref id, ref e), downloads, DownloadsMsg::Failed(*id, e.clone()));
+        connect!(downloads@DownloadsMsg::Cancel(ref id), content, MoonrenderMsg::CancelDownload(*id));
+        connect!(downloads@DownloadsMsg::Restart(ref url, ref path), self.model.relm, Msg::Download(url.clone(), path.clone()));
+
+        content.emit(MoonrenderMsg::SetFallbackEncoding(
+            crate::CONFIG.fallback_encoding.clone(),
+        ));
+
+        let url = std::env::args()
+            .nth(1)
+            .unwrap_or_else(|| crate::CONFIG.homepage.clone());
         self.model.relm.stream().emit(Msg::Goto(url));
     }
 
@@ -93,6 +173,7 @@ impl Widget for Win {
                 self.content.emit(MoonrenderMsg::Goto(url.clone()));
 
                 self.status.show();
+                self.status.remove_all(self.model.status_ctx_download);
 
                 self.status.remove_all(self.model.status_ctx_goto);
                 self.status
@@ -127,6 +208,131 @@ impl Widget for Win {
                 d.show();
             }
 
+            Msg::OpenNewWindow(url) => {
+                // every window is its own process, there are no tabs yet
+                let spawned = std::env::current_exe()
+                    .and_then(|exe| std::process::Command::new(exe).arg(&url).spawn());
+
+                if let Err(e) = spawned {
+                    log::error!("Cannot open {} in a new window: {}", url, e);
+                }
+            }
+
+            Msg::Unsupported(mime, name) => {
+                // parameters like the charset don't matter here
+                let mime = mime
+                    .split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_ascii_lowercase();
+
+                // opening runs whatever handles the type, so only types that
+                // can't run code are opened without asking
+                match self.model.mime_actions.get(&mime) {
+                    Some(MimeAction::Save) => {
+                        self.save_body(MimeAction::Save, &name, false);
+                        return;
+                    }
+                    Some(MimeAction::Open) => {
+                        if let Some(name) = relm_moonrender::open_name(&name, &mime) {
+                            self.save_body(MimeAction::Open, &name, false);
+                            return;
+                        }
+                    }
+                    None => {}
+                }
+
+                let d = gtk::MessageDialog::new(
+                    Some(&self.window),
+                    gtk::DialogFlags::all(),
+                    gtk::MessageType::Question,
+                    gtk::ButtonsType::None,
+                    &format!("Moonlander cannot show {} pages.\n\nDo you want to save {} or open it with another application?", mime, name),
+                );
+
+                d.set_title("Unsupported Page");
+                d.add_button("_Cancel", gtk::ResponseType::Cancel);
+                d.add_button("_Open", gtk::ResponseType::Apply);
+                d.add_button("_Save…", gtk::ResponseType::Accept);
+
+                let remember =
+                    gtk::CheckButton::new_with_label(&format!("Always do this for {}", mime));
+                if relm_moonrender::open_name(&name, &mime).is_none() {
+                    remember.set_tooltip_text(Some(
+                        "Opening is still asked about, these pages could run programs",
+                    ));
+                }
+                d.get_content_area().add(&remember);
+                remember.show();
+
+                let relm = self.model.relm.clone();
+                d.connect_response(move |d, resp| {
+                    let action = match resp {
+                        gtk::ResponseType::Accept => Some(MimeAction::Save),
+                        gtk::ResponseType::Apply => Some(MimeAction::Open),
+                        _ => None,
+                    };
+
+                    relm.stream().emit(Msg::UnsupportedChoice(
+                        mime.clone(),
+                        name.clone(),
+                        action,
+                        remember.get_active(),
+                    ));
+
+                    d.destroy();
+                });
+
+                d.show();
+            }
+
+            Msg::UnsupportedChoice(mime, name, action, remember) => {
+                let action = match action {
+                    Some(action) => action,
+                    None => {
+                        self.content.emit(MoonrenderMsg::DiscardBody);
+                        return;
+                    }
+                };
+
+                if remember {
+                    self.model.mime_actions.insert(mime.clone(), action);
+
+                    if let Err(e) = config::remember_mime_action(&mime, action) {
+                        log::error!("Cannot remember what to do with {}: {}", mime, e);
+                    }
+                }
+
+                self.save_body(action, &name, true);
+            }
+
+            Msg::BodyProgress(received) => {
+                self.status.show();
+
+                self.status.remove_all(self.model.status_ctx_download);
+                self.status.push(
+                    self.model.status_ctx_download,
+                    &format!("Downloaded {}", format_size(received)),
+                );
+            }
+
+            Msg::BodySaved(path, open) => {
+                self.status.show();
+
+                self.status.remove_all(self.model.status_ctx_download);
+                self.status.push(
+                    self.model.status_ctx_download,
+                    &format!("Saved {}", path.to_string_lossy()),
+                );
+
+                if open {
+                    if let Err(e) = webbrowser::open(&path.to_string_lossy()) {
+                        log::error!("Cannot open {}: {}", path.to_string_lossy(), e);
+                    }
+                }
+            }
+
             Msg::Redirect(url) => {
                 self.model.header.emit(HeaderMsg::Redirect(url.clone()));
 
@@ -225,6 +431,98 @@ impl Widget for Win {
                     self.model.header.emit(HeaderMsg::Redirect(url));
                 }
             }
+
+            Msg::Print => {
+                let theme = crate::CONFIG.print_theme.clone();
+                self.content.emit(MoonrenderMsg::Print(theme));
+            }
+
+            Msg::SavePage => {
+                let url = match self.model.history.last() {
+                    Some(url) => url.clone(),
+                    None => return,
+                };
+
+                let name = url::Url::parse(&url)
+                    .map(|url| relm_moonrender::file_name(&url))
+                    .unwrap_or_else(|_| "download".to_owned());
+
+                let path = match self.choose_save_path(&name) {
+                    Some(path) => path,
+                    None => return,
+                };
+
+                self.model.relm.stream().emit(Msg::Download(url, path));
+            }
+            Msg::Download(url, path) => {
+                let id = self.model.next_download;
+                self.model.next_download += 1;
+
+                self.model
+                    .downloads
+                    .emit(DownloadsMsg::Start(id, url.clone(), path.clone()));
+                self.model.downloads.emit(DownloadsMsg::Show);
+
+                self.content.emit(MoonrenderMsg::Download(id, url, path));
+            }
+
+            Msg::ShowDownloads => self.model.downloads.emit(DownloadsMsg::Show),
+
+            Msg::KeyPress(e) => {
+                let is_ctrl = e.get_state().contains(gdk::ModifierType::CONTROL_MASK);
+
+                match e.get_keyval() {
+                    key::f | key::F if is_ctrl => self.model.relm.stream().emit(Msg::OpenFind),
+                    key::p | key::P if is_ctrl => self.model.relm.stream().emit(Msg::Print),
+                    key::s | key::S if is_ctrl => self.model.relm.stream().emit(Msg::SavePage),
+                    key::y | key::Y if is_ctrl => self.model.relm.stream().emit(Msg::ShowDownloads),
+                    _ => {}
+                }
+            }
+
+            Msg::OpenFind => {
+                self.search_bar.set_search_mode(true);
+                self.search_entry.grab_focus();
+            }
+
+            Msg::Find => {
+                let query = self
+                    .search_entry
+                    .get_text()
+                    .map(|text| text.to_string())
+                    .unwrap_or_default();
+
+                if query.is_empty() {
+                    self.content.emit(MoonrenderMsg::FindClear);
+                    self.model.find_status = String::new();
+                    return;
+                }
+
+                let options = FindOptions {
+                    case_sensitive: self.find_case.get_active(),
+                    whole_word: self.find_word.get_active(),
+                };
+
+                self.content.emit(MoonrenderMsg::Find(query, options));
+            }
+
+            Msg::FindNext => self.content.emit(MoonrenderMsg::FindNext),
+            Msg::FindPrevious => self.content.emit(MoonrenderMsg::FindPrevious),
+
+            Msg::FindModeChanged => {
+                if !self.search_bar.get_search_mode() {
+                    self.content.emit(MoonrenderMsg::FindClear);
+                    self.model.find_status = String::new();
+                }
+            }
+
+            Msg::FindResult(current, count) => {
+                self.model.find_status = match (current, count) {
+                    (_, 0) => "No matches".to_owned(),
+                    (Some(current), count) => format!("{} of {}", current + 1, count),
+                    (None, count) => format!("{} matches", count),
+                };
+            }
         }
     }
 
@@ -236,6 +534,54 @@ impl Widget for Win {
             gtk::Box {
                 orientation: gtk::Orientation::Vertical,
 
+                #[name="search_bar"]
+                gtk::SearchBar {
+                    show_close_button: true,
+
+                    property_search_mode_enabled_notify(_) => Msg::FindModeChanged,
+
+                    gtk::Box {
+                        orientation: gtk::Orientation::Horizontal,
+                        spacing: 6,
+
+                        #[name="search_entry"]
+                        gtk::SearchEntry {
+                            placeholder_text: Some("Find in page..."),
+
+                            search_changed(_) => Msg::Find,
+                            activate(_) => Msg::FindNext,
+                            next_match(_) => Msg::FindNext,
+                            previous_match(_) => Msg::FindPrevious,
+                        },
+
+                        gtk::Button {
+                            image: Some(&gtk::Image::new_from_icon_name(Some("go-up"), gtk::IconSize::SmallToolbar)),
+                            clicked => Msg::FindPrevious,
+                        },
+
+                        gtk::Button {
+                            image: Some(&gtk::Image::new_from_icon_name(Some("go-down"), gtk::IconSize::SmallToolbar)),
+                            clicked => Msg::FindNext,
+                        },
+
+                        #[name="find_case"]
+                        gtk::CheckButton {
+                            label: "Match case",
+                            toggled => Msg::Find,
+                        },
+
+                        #[name="find_word"]
+                        gtk::CheckButton {
+                            label: "Whole words",
+                            toggled => Msg::Find,
+                        },
+
+                        gtk::Label {
+                            text: &self.model.find_status,
+                        },
+                    },
+                },
+
                 #[name="content"]
                 Moonrender(crate::CONFIG.theme.clone()) {
                     child: {
@@ -248,6 +594,94 @@ impl Widget for Win {
             },
 
             delete_event(_, _) => (Msg::Quit, Inhibit(false)),
+            key_press_event(_, e) => (Msg::KeyPress(e.clone()), Inhibit(false)),
+        }
+    }
+}
+
+impl Win {
+    // hands the unsupported page to the content to save. files to open go to
+    // a directory only the user can read, saved ones to the downloads
+    // directory unless the user picks a place. taken names get a number
+    // instead of being replaced
+    fn save_body(&mut self, action: MimeAction, name: &str, ask: bool) {
+        let (path, open) = match action {
+            MimeAction::Open => match open_dir() {
+                Ok(dir) => (dir.join(name), true),
+                Err(e) => {
+                    log::error!("Cannot create a directory for files to open: {}", e);
+                    self.content.emit(MoonrenderMsg::DiscardBody);
+                    return;
+                }
+            },
+            MimeAction::Save if !ask => (download_dir().join(name), false),
+            MimeAction::Save => match self.choose_save_path(name) {
+                Some(path) => (path, false),
+                None => {
+                    self.content.emit(MoonrenderMsg::DiscardBody);
+                    return;
+                }
+            },
+        };
+
+        self.content.emit(MoonrenderMsg::SaveBody(path, open));
+    }
+
+    fn choose_save_path(&self, name: &str) -> Option<PathBuf> {
+        let chooser = gtk::FileChooserDialog::with_buttons(
+            Some("Save Page"),
+            Some(&self.window),
+            gtk::FileChooserAction::Save,
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("_Save", gtk::ResponseType::Accept),
+            ],
+        );
+
+        chooser.set_current_folder(download_dir());
+        chooser.set_current_name(name);
+        chooser.set_do_overwrite_confirmation(true);
+
+        let path = if chooser.run() == gtk::ResponseType::Accept {
+            chooser.get_filename()
+        } else {
+            None
+        };
+
+        chooser.destroy();
+
+        // the chooser asked before replacing the file
+        if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+            if let Err(e) = std::fs::remove_file(path) {
+                log::error!("Cannot replace {}: {}", path.to_string_lossy(), e);
+                return None;
+            }
         }
+
+        path
     }
 }
+
+// private to the user, unlike the shared temporary directory
+fn open_dir() -> std::io::Result<PathBuf> {
+    let dir = crate::DIRS.cache_dir().join("open");
+
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+fn download_dir() -> PathBuf {
+    directories_next::UserDirs::new()
+        .map(|dirs| {
+            dirs.download_dir()
+                .unwrap_or_else(|| dirs.home_dir())
+                .to_path_buf()
+        })
+        .unwrap_or_else(std::env::temp_dir)
+}
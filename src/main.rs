@@ -1,6 +1,8 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod config;
+mod downloads;
+mod export;
 mod gui;
 
 use anyhow::{anyhow, Result};
@@ -26,5 +28,12 @@ fn main() -> Result<()> {
 
     log::info!("Hello, moon!");
 
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, flag, path, url] = args.as_slice() {
+        if flag == "--export" {
+            return export::export(path.as_ref(), url);
+        }
+    }
+
     gui::Win::run(()).map_err(|_| anyhow!("Cannot run GTK application"))
 }